@@ -0,0 +1,83 @@
+// Compares routing queries on the mutable adjacency lists with the same graph
+// frozen into compressed sparse row form. Run with `cargo bench`.
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+// The shared test helpers build on these too
+use adj_list::{Direction, GraphMode, InternetOfThings, Multiplicity};
+
+// Only the generator is needed here
+#[allow(dead_code)]
+#[path = "../src/test_support.rs"]
+mod test_support;
+
+use test_support::Lcg;
+
+const NODES: u64 = 50_000;
+const EDGES_PER_NODE: u64 = 8;
+const SOURCES: u64 = 20;
+
+// Edges are added round-robin so the per-node lists end up scattered over the heap
+fn build() -> InternetOfThings
+{
+    let mut rng = Lcg(42);
+    let mut g = InternetOfThings::new();
+    g.set_nodes((0..NODES).collect());
+    for _ in 0..EDGES_PER_NODE
+    {
+        for n in 0..NODES
+        {
+            g.add_edge(n, rng.below(NODES), 1 + rng.below(100) as u32).unwrap();
+        }
+    }
+    g
+}
+
+fn time(name: &str, mut run: impl FnMut()) -> Duration
+{
+    run();
+    let start = Instant::now();
+    run();
+    let elapsed = start.elapsed();
+    println!("{:<32} {:>10.2?}", name, elapsed);
+    elapsed
+}
+
+fn main()
+{
+    let g = build();
+    let frozen = g.clone().freeze();
+    let sources: Vec<u64> = (0..SOURCES).map(|i| i * (NODES / SOURCES)).collect();
+
+    let lists = time("dijkstra, adjacency lists", ||
+    {
+        for s in &sources
+        {
+            black_box(g.shorted_path(*s, NODES - 1 - *s));
+        }
+    });
+    let csr = time("dijkstra, frozen", ||
+    {
+        for s in &sources
+        {
+            black_box(frozen.shorted_path(*s, NODES - 1 - *s));
+        }
+    });
+    println!("{:<32} {:>10.2}x", "speed-up", lists.as_secs_f64() / csr.as_secs_f64());
+
+    let lists = time("bfs 4 hops, adjacency lists", ||
+    {
+        for s in &sources
+        {
+            black_box(g.connected_with_distance(*s, 4));
+        }
+    });
+    let csr = time("bfs 4 hops, frozen", ||
+    {
+        for s in &sources
+        {
+            black_box(frozen.connected_with_distance(*s, 4));
+        }
+    });
+    println!("{:<32} {:>10.2}x", "speed-up", lists.as_secs_f64() / csr.as_secs_f64());
+}
@@ -0,0 +1,151 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::hash::Hash;
+
+use super::traversal::{forward_to_view, GraphView};
+use super::{NonNegative, TentativeWeight};
+
+forward_to_view!
+{
+    [K: Hash + Eq + Clone, W: NonNegative] (K, W)
+    fn a_star(&self, from: K, to: K, heuristic: impl Fn(K, K) -> W) -> Option<(W, Vec<K>)>;
+    fn a_star_by_ref(&self, from: K, to: K, heuristic: impl Fn(&K, &K) -> W)
+        -> Option<(W, Vec<K>)>;
+}
+
+impl<'a, K: Hash + Eq + Clone, W: NonNegative> GraphView<'a, K, W>
+{
+    // The heuristic estimates the remaining cost from a node to the target.
+    // As long as it never overestimates, the result matches `shorted_path`.
+    pub(crate) fn a_star(&self, from: K, to: K, heuristic: impl Fn(K, K) -> W)
+        -> Option<(W, Vec<K>)>
+    {
+        self.a_star_by_ref(from, to, |node, target| heuristic(node.clone(), target.clone()))
+    }
+
+    // Same as `a_star`, but the heuristic borrows the keys instead of
+    // getting a copy of them on every call, which matters for string keys
+    pub(crate) fn a_star_by_ref(
+        &self,
+        from: K,
+        to: K,
+        heuristic: impl Fn(&K, &K) -> W
+    ) -> Option<(W, Vec<K>)>
+    {
+        let src = self.get_node_index(&from)?;
+        let dst = self.get_node_index(&to)?;
+
+        let mut distance: Vec<TentativeWeight<W>> =
+            vec![TentativeWeight::Infinite; self.nodes.len()];
+        distance[src] = TentativeWeight::Number(W::zero());
+        let mut parent = vec![None; self.nodes.len()];
+
+        // Ordered by estimated total cost, then by the cost so far. A node is
+        // pushed again whenever a cheaper way to it turns up, which keeps
+        // inconsistent (but admissible) heuristics correct.
+        let mut open = BinaryHeap::new();
+        let estimate = TentativeWeight::Number(heuristic(&from, &to));
+        open.push(Reverse((estimate, TentativeWeight::Number(W::zero()), src)));
+
+        while let Some(Reverse((_, dist, u))) = open.pop()
+        {
+            if dist > distance[u]
+            {
+                continue;
+            }
+
+            if u == dst
+            {
+                return match dist
+                {
+                    TentativeWeight::Number(n) => Some((n, self.build_path(&parent, dst))),
+                    _ => None
+                };
+            }
+
+            for e in self.adjacent(u)
+            {
+                let new_distance = dist.add(e.weight);
+                if new_distance < distance[e.node]
+                {
+                    distance[e.node] = new_distance.clone();
+                    parent[e.node] = Some(u);
+                    let estimate = new_distance.add(heuristic(&self.nodes[e.node], &to));
+                    open.push(Reverse((estimate, new_distance, e.node)));
+                }
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use crate::test_support::Lcg;
+    use crate::{InternetOfThings, KeyType};
+
+    fn manhattan(a: (u64, u64), b: (u64, u64)) -> u32
+    {
+        (a.0.max(b.0) - a.0.min(b.0) + a.1.max(b.1) - a.1.min(b.1)) as u32
+    }
+
+    #[test]
+    fn a_star_on_a_line()
+    {
+        let mut g = InternetOfThings::new();
+        g.set_nodes(vec![1, 2, 3, 4]);
+        g.set_edges(1, vec![(1, 2), (10, 4)]);
+        g.set_edges(2, vec![(1, 3)]);
+        g.set_edges(3, vec![(1, 4)]);
+        assert_eq!(g.a_star(1, 4, |_, _| 0), Some((3, vec![1, 2, 3, 4])));
+        assert_eq!(g.a_star(4, 1, |_, _| 0), None);
+        assert_eq!(g.a_star(1, 1, |_, _| 0), Some((0, vec![1])));
+        assert_eq!(g.a_star(1, 99, |_, _| 0), None);
+    }
+
+    #[test]
+    fn a_star_matches_dijkstra_with_admissible_heuristic()
+    {
+        let mut rng = Lcg(1234);
+        for _ in 0..10
+        {
+            // Nodes on a grid; every edge costs at least the Manhattan distance
+            let coordinates: Vec<(u64, u64)> =
+                (0..80).map(|_| (rng.below(50), rng.below(50))).collect();
+            let mut g = InternetOfThings::new();
+            g.set_nodes((0..80).collect());
+            for n in 0..80u64
+            {
+                let edges = (0..rng.below(5))
+                    .map(|_|
+                    {
+                        let to = rng.below(80);
+                        let cost = manhattan(coordinates[n as usize], coordinates[to as usize]);
+                        (cost + rng.below(10) as u32, to)
+                    })
+                    .collect();
+                g.set_edges(n, edges);
+            }
+
+            let heuristic = |a: KeyType, b: KeyType|
+            {
+                manhattan(coordinates[a as usize], coordinates[b as usize])
+            };
+            for _ in 0..40
+            {
+                let (from, to) = (rng.below(80), rng.below(80));
+                let expected = g.shorted_path(from, to);
+                let actual = g.a_star(from, to, heuristic);
+                assert_eq!(actual.as_ref().map(|r| r.0), expected.as_ref().map(|r| r.0));
+                let by_ref = g.a_star_by_ref(from, to, |a, b| heuristic(*a, *b));
+                assert_eq!(by_ref, actual);
+                if let Some((_, path)) = actual
+                {
+                    assert_eq!(path.first(), Some(&from));
+                    assert_eq!(path.last(), Some(&to));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,523 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::error::Error;
+use std::fmt;
+use std::hash::Hash;
+
+use super::traversal::{forward_to_view, GraphView};
+use super::KeyType;
+
+// Returned when a graph isn't bipartite: an odd cycle proving it, with the
+// first node repeated at the end. A self loop is the cycle [a, a].
+#[derive(Debug, Clone, PartialEq)]
+pub struct OddCycle<K = KeyType>(pub Vec<K>);
+
+impl<K: fmt::Debug> fmt::Display for OddCycle<K>
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        write!(f, "The graph is not bipartite, it has the odd cycle {:?}", self.0)
+    }
+}
+
+impl<K: fmt::Debug> Error for OddCycle<K> {}
+
+// Colourings and matchings ignore edge directions: a link in either
+// direction keeps two devices from sharing a colour. Self loops are ignored
+// when colouring.
+forward_to_view!
+{
+    [K: Hash + Eq + Clone, W: Clone] (K, W)
+    fn greedy_colouring(&self) -> HashMap<K, usize>;
+    fn dsatur_colouring(&self) -> HashMap<K, usize>;
+    fn is_bipartite(&self) -> bool;
+    fn bipartition(&self) -> Result<(Vec<K>, Vec<K>), OddCycle<K>>;
+    fn maximum_matching(&self, left: &[K], right: &[K]) -> Vec<(K, K)>;
+}
+
+impl<'a, K: Hash + Eq + Clone, W: Clone> GraphView<'a, K, W>
+{
+    // First colour not taken by a neighbour, in node order
+    pub(crate) fn greedy_colouring(&self) -> HashMap<K, usize>
+    {
+        let neighbours = self.neighbour_sets();
+        let mut colour: Vec<Option<usize>> = vec![None; self.nodes.len()];
+        for u in 0..self.nodes.len()
+        {
+            let taken: HashSet<usize> = neighbours[u].iter().filter_map(|v| colour[*v]).collect();
+            colour[u] = (0..).find(|c| !taken.contains(c));
+        }
+        self.by_key(colour.into_iter().flatten().collect())
+    }
+
+    // Brélaz's DSatur: colour the node with the most differently coloured
+    // neighbours next, breaking ties by degree. Optimal on bipartite graphs.
+    pub(crate) fn dsatur_colouring(&self) -> HashMap<K, usize>
+    {
+        let neighbours = self.neighbour_sets();
+        let n = self.nodes.len();
+        let mut colour: Vec<Option<usize>> = vec![None; n];
+        let mut saturation: Vec<HashSet<usize>> = vec![HashSet::new(); n];
+
+        for _ in 0..n
+        {
+            let u = (0..n)
+                .filter(|u| colour[*u].is_none())
+                .max_by_key(|u| (saturation[*u].len(), neighbours[*u].len(), n - u))
+                .expect("an uncoloured node is left each round");
+            let c = (0..).find(|c| !saturation[u].contains(c)).unwrap_or(0);
+            colour[u] = Some(c);
+            for v in neighbours[u].iter()
+            {
+                saturation[*v].insert(c);
+            }
+        }
+        self.by_key(colour.into_iter().flatten().collect())
+    }
+
+    pub(crate) fn is_bipartite(&self) -> bool
+    {
+        self.bipartition().is_ok()
+    }
+
+    // Splits the nodes into two sides with every edge running between them
+    pub(crate) fn bipartition(&self) -> Result<(Vec<K>, Vec<K>), OddCycle<K>>
+    {
+        let neighbours = self.neighbour_sets();
+        if let Some((u, _)) = self.edge_list().find(|(from, e)| *from == e.node)
+        {
+            return Err(OddCycle(vec![self.nodes[u].clone(), self.nodes[u].clone()]));
+        }
+
+        let n = self.nodes.len();
+        let mut side: Vec<Option<bool>> = vec![None; n];
+        let mut parent: Vec<Option<usize>> = vec![None; n];
+        for root in 0..n
+        {
+            if side[root].is_some()
+            {
+                continue;
+            }
+            side[root] = Some(false);
+            let mut queue: VecDeque<usize> = vec![root].into_iter().collect();
+            while let Some(u) = queue.pop_front()
+            {
+                for v in neighbours[u].iter().copied()
+                {
+                    match side[v]
+                    {
+                        None =>
+                        {
+                            side[v] = side[u].map(|s| !s);
+                            parent[v] = Some(u);
+                            queue.push_back(v);
+                        }
+                        Some(s) if Some(s) == side[u] =>
+                        {
+                            return Err(OddCycle(self.odd_cycle(&parent, u, v)));
+                        }
+                        Some(_) => {}
+                    }
+                }
+            }
+        }
+
+        let (mut left, mut right) = (vec![], vec![]);
+        for (i, s) in side.into_iter().enumerate()
+        {
+            match s
+            {
+                Some(true) => right.push(self.nodes[i].clone()),
+                _ => left.push(self.nodes[i].clone())
+            }
+        }
+        Ok((left, right))
+    }
+
+    // Hopcroft-Karp maximum matching between the `left` and `right` nodes,
+    // using only edges that run between the two sets. Pairs are (left, right).
+    pub(crate) fn maximum_matching(&self, left: &[K], right: &[K]) -> Vec<(K, K)>
+    {
+        let left: Vec<usize> = self.known_unique(left);
+        let right_nodes: Vec<usize> = self.known_unique(right);
+        let left_set: HashSet<usize> = left.iter().copied().collect();
+        let mut right_pos = vec![None; self.nodes.len()];
+        for (pos, r) in right_nodes.iter().enumerate()
+        {
+            if !left_set.contains(r)
+            {
+                right_pos[*r] = Some(pos);
+            }
+        }
+
+        let neighbours = self.neighbour_sets();
+        let adjacent: Vec<Vec<usize>> = left.iter()
+            .map(|u| neighbours[*u].iter().filter_map(|v| right_pos[*v]).collect())
+            .collect();
+
+        let (pair_left, _) = hopcroft_karp(&adjacent, right_nodes.len());
+        pair_left.into_iter().enumerate().filter_map(|(l, r)|
+        {
+            r.map(|r| (self.nodes[left[l]].clone(), self.nodes[right_nodes[r]].clone()))
+        }).collect()
+    }
+
+    // Undirected neighbours of every node, without self loops or repeats
+    fn neighbour_sets(&self) -> Vec<Vec<usize>>
+    {
+        let mut neighbours: Vec<HashSet<usize>> = vec![HashSet::new(); self.nodes.len()];
+        for (from, e) in self.edge_list()
+        {
+            if from != e.node
+            {
+                neighbours[from].insert(e.node);
+                neighbours[e.node].insert(from);
+            }
+        }
+        neighbours.into_iter().map(|set|
+        {
+            let mut list: Vec<usize> = set.into_iter().collect();
+            list.sort_unstable();
+            list
+        }).collect()
+    }
+
+    // Closes the cycle formed by the BFS tree paths to u and v and the edge u - v
+    fn odd_cycle(&self, parent: &[Option<usize>], u: usize, v: usize) -> Vec<K>
+    {
+        let to_root = |mut n: usize|
+        {
+            let mut path = vec![n];
+            while let Some(p) = parent[n]
+            {
+                path.push(p);
+                n = p;
+            }
+            path
+        };
+        let (mut from_u, mut from_v) = (to_root(u), to_root(v));
+        // Drop the shared part above the lowest common ancestor
+        while from_u.len() > 1 && from_v.len() > 1
+            && from_u[from_u.len() - 2] == from_v[from_v.len() - 2]
+        {
+            from_u.pop();
+            from_v.pop();
+        }
+        from_v.pop();
+        from_v.reverse();
+        from_u.extend(from_v);
+        from_u.push(u);
+        from_u.into_iter().map(|n| self.nodes[n].clone()).collect()
+    }
+
+    fn known_unique(&self, keys: &[K]) -> Vec<usize>
+    {
+        let mut seen = HashSet::new();
+        keys.iter()
+            .filter_map(|k| self.get_node_index(k))
+            .filter(|i| seen.insert(*i))
+            .collect()
+    }
+}
+
+// Returns the partner of every left and every right node
+fn hopcroft_karp(adjacent: &[Vec<usize>], right: usize) -> (Vec<Option<usize>>, Vec<Option<usize>>)
+{
+    let left = adjacent.len();
+    let mut pair_left: Vec<Option<usize>> = vec![None; left];
+    let mut pair_right: Vec<Option<usize>> = vec![None; right];
+    let mut layer = vec![usize::MAX; left];
+
+    loop
+    {
+        // Layer the free left nodes and everything reachable by alternating paths
+        let mut queue = VecDeque::new();
+        for u in 0..left
+        {
+            layer[u] = match pair_left[u]
+            {
+                None =>
+                {
+                    queue.push_back(u);
+                    0
+                }
+                Some(_) => usize::MAX
+            };
+        }
+        let mut augmentable = false;
+        while let Some(u) = queue.pop_front()
+        {
+            for v in adjacent[u].iter()
+            {
+                match pair_right[*v]
+                {
+                    None => augmentable = true,
+                    Some(w) if layer[w] == usize::MAX =>
+                    {
+                        layer[w] = layer[u] + 1;
+                        queue.push_back(w);
+                    }
+                    Some(_) => {}
+                }
+            }
+        }
+        if !augmentable
+        {
+            return (pair_left, pair_right);
+        }
+
+        // Vertex-disjoint shortest augmenting paths, walked without recursion
+        let mut next = vec![0; left];
+        for root in 0..left
+        {
+            if pair_left[root].is_some()
+            {
+                continue;
+            }
+            let mut stack = vec![root];
+            let mut via: Vec<usize> = vec![];
+            while let Some(u) = stack.last().copied()
+            {
+                if next[u] == adjacent[u].len()
+                {
+                    // Dead end, don't try this node again in this phase
+                    layer[u] = usize::MAX;
+                    stack.pop();
+                    via.pop();
+                    continue;
+                }
+                let v = adjacent[u][next[u]];
+                next[u] += 1;
+                match pair_right[v]
+                {
+                    None =>
+                    {
+                        via.push(v);
+                        for (l, r) in stack.iter().zip(via.iter())
+                        {
+                            pair_left[*l] = Some(*r);
+                            pair_right[*r] = Some(*l);
+                        }
+                        break;
+                    }
+                    Some(w) if layer[w] == layer[u] + 1 =>
+                    {
+                        via.push(v);
+                        stack.push(w);
+                    }
+                    Some(_) => {}
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::test_support::{random_undirected, Lcg};
+    use crate::{Direction, GraphMode, InternetOfThings, Multiplicity};
+
+    fn undirected() -> InternetOfThings
+    {
+        InternetOfThings::with_mode(GraphMode::new(Direction::Undirected, Multiplicity::Multi))
+    }
+
+    fn assert_proper(g: &InternetOfThings, colours: &HashMap<KeyType, usize>)
+    {
+        assert_eq!(colours.len(), g.nodes());
+        for (from, e) in g.edge_list()
+        {
+            let (a, b) = (g.nodes[from], g.nodes[e.node]);
+            assert!(a == b || colours[&a] != colours[&b], "{} and {} share a colour", a, b);
+        }
+    }
+
+    fn colour_count(colours: &HashMap<KeyType, usize>) -> usize
+    {
+        colours.values().collect::<HashSet<_>>().len()
+    }
+
+    // Simple augmenting path search to check Hopcroft-Karp against
+    fn kuhn(adjacent: &[Vec<usize>], right: usize) -> usize
+    {
+        fn augment(
+            u: usize,
+            adj: &[Vec<usize>],
+            seen: &mut [bool],
+            pair: &mut [Option<usize>]
+        ) -> bool
+        {
+            for v in &adj[u]
+            {
+                if !seen[*v]
+                {
+                    seen[*v] = true;
+                    if pair[*v].is_none_or(|w| augment(w, adj, seen, pair))
+                    {
+                        pair[*v] = Some(u);
+                        return true;
+                    }
+                }
+            }
+            false
+        }
+        let mut pair = vec![None; right];
+        (0..adjacent.len())
+            .filter(|u| augment(*u, adjacent, &mut vec![false; right], &mut pair))
+            .count()
+    }
+
+    #[test]
+    fn colourings_are_proper()
+    {
+        let mut rng = Lcg(11);
+        for _ in 0..20
+        {
+            let g = random_undirected(&mut rng, 30, 60, 1);
+            let greedy = g.greedy_colouring();
+            let dsatur = g.dsatur_colouring();
+            assert_proper(&g, &greedy);
+            assert_proper(&g, &dsatur);
+        }
+
+        // Directed edges constrain colours too
+        let mut directed = InternetOfThings::new();
+        directed.set_nodes(vec![1, 2, 3]);
+        directed.set_edges(1, vec![(1, 2), (1, 3)]);
+        directed.set_edges(2, vec![(1, 3)]);
+        assert_eq!(colour_count(&directed.dsatur_colouring()), 3);
+    }
+
+    #[test]
+    fn dsatur_beats_greedy_on_crown_graph()
+    {
+        // u_i - v_j for i != j, listed u_1, v_1, u_2, v_2, ...
+        let mut g = undirected();
+        g.set_nodes((0..12).collect());
+        for i in 0..6
+        {
+            for j in 0..6
+            {
+                if i != j
+                {
+                    g.add_edge(2 * i, 2 * j + 1, 1).unwrap();
+                }
+            }
+        }
+        assert_eq!(colour_count(&g.greedy_colouring()), 6);
+        assert_eq!(colour_count(&g.dsatur_colouring()), 2);
+    }
+
+    #[test]
+    fn bipartition_or_odd_cycle()
+    {
+        let mut g = undirected();
+        g.set_nodes((1..=6).collect());
+        for (a, b) in [(1, 2), (2, 3), (3, 4), (4, 1), (5, 6)]
+        {
+            g.add_edge(a, b, 1).unwrap();
+        }
+        let (left, right) = g.bipartition().unwrap();
+        assert_eq!(left, vec![1, 3, 5]);
+        assert_eq!(right, vec![2, 4, 6]);
+
+        g.add_edge(4, 6, 1).unwrap();
+        g.add_edge(6, 2, 1).unwrap();
+        assert!(g.is_bipartite());
+        g.add_edge(3, 6, 1).unwrap();
+        let cycle = g.bipartition().unwrap_err().0;
+        assert_eq!(cycle.first(), cycle.last());
+        assert_eq!((cycle.len() - 1) % 2, 1);
+        for pair in cycle.windows(2)
+        {
+            assert!(g.connected(pair[0], 1).unwrap().contains(&pair[1]));
+        }
+        assert!(!g.is_bipartite());
+
+        let mut looped = undirected();
+        looped.set_nodes(vec![1]);
+        looped.add_edge(1, 1, 1).unwrap();
+        assert_eq!(looped.bipartition(), Err(OddCycle(vec![1, 1])));
+    }
+
+    #[test]
+    fn odd_cycles_on_random_graphs()
+    {
+        let mut rng = Lcg(23);
+        for _ in 0..50
+        {
+            let g = random_undirected(&mut rng, 12, 10, 1);
+            match g.bipartition()
+            {
+                Ok((left, right)) =>
+                {
+                    let side: HashSet<KeyType> = left.iter().copied().collect();
+                    assert_eq!(left.len() + right.len(), 12);
+                    for (from, e) in g.edge_list()
+                    {
+                        let (a, b) = (g.nodes[from], g.nodes[e.node]);
+                        assert_ne!(side.contains(&a), side.contains(&b));
+                    }
+                }
+                Err(OddCycle(cycle)) =>
+                {
+                    assert_eq!(cycle.first(), cycle.last());
+                    assert_eq!(cycle.len() % 2, 0);
+                    let distinct: HashSet<_> = cycle[1..].iter().collect();
+                    assert_eq!(distinct.len(), cycle.len() - 1);
+                    for pair in cycle.windows(2)
+                    {
+                        assert!(g.connected(pair[0], 1).unwrap().contains(&pair[1]));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn sensors_to_collectors()
+    {
+        let mut g = InternetOfThings::new();
+        g.set_nodes(vec![1, 2, 3, 10, 20, 30]);
+        g.set_edges(1, vec![(1, 10), (1, 20)]);
+        g.set_edges(2, vec![(1, 10), (1, 2)]);
+        g.set_edges(30, vec![(1, 3), (1, 1)]);
+        let matching = g.maximum_matching(&[1, 2, 3, 99], &[10, 20, 30]);
+        assert_eq!(matching.len(), 3);
+        let mut matched: Vec<KeyType> = matching.iter().map(|(_, r)| *r).collect();
+        matched.sort();
+        assert_eq!(matched, vec![10, 20, 30]);
+        assert!(matching.contains(&(3, 30)));
+        assert!(matching.contains(&(2, 10)));
+    }
+
+    #[test]
+    fn hopcroft_karp_is_maximum()
+    {
+        let mut rng = Lcg(31);
+        for _ in 0..100
+        {
+            let (left, right) = (1 + rng.below(15) as usize, 1 + rng.below(15) as usize);
+            let adjacent: Vec<Vec<usize>> = (0..left)
+                .map(|_|
+                {
+                    let mut adj: Vec<usize> = (0..right).filter(|_| rng.below(4) == 0).collect();
+                    adj.dedup();
+                    adj
+                })
+                .collect();
+            let (pair_left, pair_right) = hopcroft_karp(&adjacent, right);
+            let size = pair_left.iter().flatten().count();
+            assert_eq!(size, kuhn(&adjacent, right));
+            for (l, r) in pair_left.iter().enumerate()
+            {
+                if let Some(r) = r
+                {
+                    assert!(adjacent[l].contains(r));
+                    assert_eq!(pair_right[*r], Some(l));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,290 @@
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+use std::hash::Hash;
+
+use super::traversal::{forward_to_view, GraphView};
+use super::KeyType;
+
+// Returned when a topological order is requested for a graph with cycles.
+// Lists every node that sits on at least one cycle.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CycleError<K = KeyType>(pub Vec<K>);
+
+impl<K: fmt::Debug> fmt::Display for CycleError<K>
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        write!(f, "The graph has cycles through the nodes {:?}", self.0)
+    }
+}
+
+impl<K: fmt::Debug> Error for CycleError<K> {}
+
+forward_to_view!
+{
+    [K: Hash + Eq + Clone, W: Clone] (K, W)
+    fn weakly_connected_components(&self) -> Vec<Vec<K>>;
+    fn strongly_connected_components(&self) -> Vec<Vec<K>>;
+    fn topological_order(&self) -> Result<Vec<K>, CycleError<K>>;
+}
+
+impl<'a, K: Hash + Eq + Clone, W: Clone> GraphView<'a, K, W>
+{
+    // Components when edge directions are ignored
+    pub(crate) fn weakly_connected_components(&self) -> Vec<Vec<K>>
+    {
+        let mut neighbours = vec![vec![]; self.nodes.len()];
+        for from in 0..self.nodes.len()
+        {
+            for e in self.adjacent(from)
+            {
+                neighbours[from].push(e.node);
+                neighbours[e.node].push(from);
+            }
+        }
+
+        let mut visited = vec![false; self.nodes.len()];
+        let mut components = vec![];
+        for start in 0..self.nodes.len()
+        {
+            if visited[start]
+            {
+                continue;
+            }
+            visited[start] = true;
+            let mut component = vec![];
+            let mut queue: VecDeque<usize> = vec![start].into_iter().collect();
+            while let Some(u) = queue.pop_front()
+            {
+                component.push(self.nodes[u].clone());
+                for v in &neighbours[u]
+                {
+                    if !visited[*v]
+                    {
+                        visited[*v] = true;
+                        queue.push_back(*v);
+                    }
+                }
+            }
+            components.push(component);
+        }
+        components
+    }
+
+    // Tarjan's algorithm. Components come out in reverse topological order,
+    // i.e. a component is listed before every component that links into it.
+    pub(crate) fn strongly_connected_components(&self) -> Vec<Vec<K>>
+    {
+        self.tarjan().into_iter().map(|c|
+        {
+            c.into_iter().map(|n| self.nodes[n].clone()).collect()
+        }).collect()
+    }
+
+    pub(crate) fn topological_order(&self) -> Result<Vec<K>, CycleError<K>>
+    {
+        let mut in_degree = vec![0usize; self.nodes.len()];
+        for u in 0..self.nodes.len()
+        {
+            for e in self.adjacent(u)
+            {
+                in_degree[e.node] += 1;
+            }
+        }
+
+        let mut queue: VecDeque<usize> = (0..self.nodes.len())
+            .filter(|n| in_degree[*n] == 0)
+            .collect();
+        let mut order = Vec::with_capacity(self.nodes.len());
+        while let Some(u) = queue.pop_front()
+        {
+            order.push(self.nodes[u].clone());
+            for e in self.adjacent(u)
+            {
+                in_degree[e.node] -= 1;
+                if in_degree[e.node] == 0
+                {
+                    queue.push_back(e.node);
+                }
+            }
+        }
+
+        if order.len() == self.nodes.len()
+        {
+            Ok(order)
+        } else {
+            let on_cycle = self.tarjan().into_iter().filter(|c|
+            {
+                c.len() > 1 || self.adjacent(c[0]).iter().any(|e| e.node == c[0])
+            }).flatten().map(|n| self.nodes[n].clone()).collect();
+            Err(CycleError(on_cycle))
+        }
+    }
+
+    // Iterative so deep dependency chains can't overflow the stack
+    fn tarjan(&self) -> Vec<Vec<usize>>
+    {
+        let mut counter = 0;
+        let mut index: Vec<Option<usize>> = vec![None; self.nodes.len()];
+        let mut low_link = vec![0; self.nodes.len()];
+        let mut on_stack = vec![false; self.nodes.len()];
+        let mut stack = vec![];
+        let mut components = vec![];
+
+        for start in 0..self.nodes.len()
+        {
+            if index[start].is_some()
+            {
+                continue;
+            }
+
+            // (node, position of the next edge to look at)
+            let mut calls = vec![(start, 0)];
+            index[start] = Some(counter);
+            low_link[start] = counter;
+            counter += 1;
+            stack.push(start);
+            on_stack[start] = true;
+
+            while let Some((u, next_edge)) = calls.last_mut()
+            {
+                let u = *u;
+                if let Some(e) = self.adjacent(u).get(*next_edge)
+                {
+                    *next_edge += 1;
+                    match index[e.node]
+                    {
+                        None =>
+                        {
+                            index[e.node] = Some(counter);
+                            low_link[e.node] = counter;
+                            counter += 1;
+                            stack.push(e.node);
+                            on_stack[e.node] = true;
+                            calls.push((e.node, 0));
+                        },
+                        Some(i) if on_stack[e.node] =>
+                            low_link[u] = low_link[u].min(i),
+                        _ => {}
+                    }
+                } else {
+                    calls.pop();
+                    if let Some((parent, _)) = calls.last()
+                    {
+                        low_link[*parent] = low_link[*parent].min(low_link[u]);
+                    }
+
+                    if Some(low_link[u]) == index[u]
+                    {
+                        let mut component = vec![];
+                        loop
+                        {
+                            let n = stack.pop().unwrap();
+                            on_stack[n] = false;
+                            component.push(n);
+                            if n == u
+                            {
+                                break;
+                            }
+                        }
+                        components.push(component);
+                    }
+                }
+            }
+        }
+        components
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::InternetOfThings;
+
+    fn sorted(mut components: Vec<Vec<KeyType>>) -> Vec<Vec<KeyType>>
+    {
+        for c in components.iter_mut()
+        {
+            c.sort();
+        }
+        components.sort();
+        components
+    }
+
+    #[test]
+    fn weak_components()
+    {
+        let mut g = InternetOfThings::new();
+        g.set_nodes(vec![1, 2, 3, 4, 5, 6]);
+        g.set_edges(1, vec![(1, 2)]);
+        g.set_edges(3, vec![(1, 2)]);
+        g.set_edges(5, vec![(1, 4)]);
+        let components = sorted(g.weakly_connected_components());
+        assert_eq!(components, vec![vec![1, 2, 3], vec![4, 5], vec![6]]);
+    }
+
+    #[test]
+    fn strong_components()
+    {
+        let mut g = InternetOfThings::new();
+        g.set_nodes(vec![1, 2, 3, 4, 5, 6]);
+        g.set_edges(1, vec![(1, 2)]);
+        g.set_edges(2, vec![(1, 3)]);
+        g.set_edges(3, vec![(1, 1), (1, 4)]);
+        g.set_edges(4, vec![(1, 5)]);
+        g.set_edges(5, vec![(1, 4), (1, 6)]);
+
+        let components = g.strongly_connected_components();
+        assert_eq!(sorted(components.clone()), vec![vec![1, 2, 3], vec![4, 5], vec![6]]);
+
+        // Reverse topological order: sinks first
+        let position = |n: KeyType| components.iter().position(|c| c.contains(&n)).unwrap();
+        assert!(position(6) < position(4));
+        assert!(position(4) < position(1));
+    }
+
+    #[test]
+    fn deep_chain_does_not_overflow()
+    {
+        let mut g = InternetOfThings::new();
+        g.set_nodes((0..100_000).collect());
+        for n in 0..99_999
+        {
+            g.set_edges(n, vec![(1, n + 1)]);
+        }
+        assert_eq!(g.strongly_connected_components().len(), 100_000);
+    }
+
+    #[test]
+    fn topological_sort()
+    {
+        let mut g = InternetOfThings::new();
+        g.set_nodes(vec![1, 2, 3, 4]);
+        g.set_edges(1, vec![(1, 2), (1, 3)]);
+        g.set_edges(2, vec![(1, 4)]);
+        g.set_edges(3, vec![(1, 4)]);
+        let order = g.topological_order().unwrap();
+        let position = |n: KeyType| order.iter().position(|o| *o == n).unwrap();
+        assert_eq!(order.len(), 4);
+        assert!(position(1) < position(2));
+        assert!(position(1) < position(3));
+        assert!(position(2) < position(4));
+        assert!(position(3) < position(4));
+    }
+
+    #[test]
+    fn topological_sort_reports_cycle()
+    {
+        let mut g = InternetOfThings::new();
+        g.set_nodes(vec![1, 2, 3, 4, 5]);
+        g.set_edges(1, vec![(1, 2)]);
+        g.set_edges(2, vec![(1, 3)]);
+        g.set_edges(3, vec![(1, 2), (1, 4)]);
+        g.set_edges(5, vec![(1, 5)]);
+        let mut cycle = g.topological_order().unwrap_err().0;
+        cycle.sort();
+        assert_eq!(cycle, vec![2, 3, 5]);
+    }
+}
@@ -0,0 +1,597 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::hash::Hash;
+
+use super::{build_path, Graph, Multiplicity, NonNegative, TentativeWeight, UnknownNodes};
+
+// A change made through an `ObservedGraph`. Undirected edges are reported
+// once, in the direction they were given in.
+#[derive(Clone, Debug, PartialEq)]
+pub enum GraphEvent<K, W>
+{
+    NodeAdded(K),
+    NodeRemoved(K),
+    EdgeAdded { from: K, to: K, weight: W },
+    EdgeRemoved { from: K, to: K, weight: W },
+    EdgeReweighted { from: K, to: K, old: W, new: W }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ObserverId(usize);
+
+type Observer<K, W> = Box<dyn FnMut(&GraphEvent<K, W>)>;
+
+// Wraps a graph so that every change goes past the registered observers and
+// keeps the shortest-path trees of tracked sources up to date. Edge changes
+// repair only the part of a tree they affect instead of rebuilding it.
+pub struct ObservedGraph<K, W>
+{
+    graph: Graph<K, W>,
+    observers: Vec<(ObserverId, Observer<K, W>)>,
+    next_observer: usize,
+    trees: HashMap<K, SourceTree<W>>
+}
+
+struct SourceTree<W>
+{
+    distance: Vec<TentativeWeight<W>>,
+    parent: Vec<Option<usize>>
+}
+
+impl<K: Hash + Eq + Clone, W: NonNegative> ObservedGraph<K, W>
+{
+    pub fn new(graph: Graph<K, W>) -> ObservedGraph<K, W>
+    {
+        ObservedGraph
+        {
+            graph,
+            observers: vec![],
+            next_observer: 0,
+            trees: HashMap::new()
+        }
+    }
+
+    pub fn graph(&self) -> &Graph<K, W>
+    {
+        &self.graph
+    }
+
+    pub fn into_inner(self) -> Graph<K, W>
+    {
+        self.graph
+    }
+
+    pub fn subscribe(&mut self, observer: impl FnMut(&GraphEvent<K, W>) + 'static) -> ObserverId
+    {
+        let id = ObserverId(self.next_observer);
+        self.next_observer += 1;
+        self.observers.push((id, Box::new(observer)));
+        id
+    }
+
+    pub fn unsubscribe(&mut self, id: ObserverId) -> bool
+    {
+        let before = self.observers.len();
+        self.observers.retain(|(o, _)| *o != id);
+        self.observers.len() != before
+    }
+
+    // Starts keeping a shortest-path tree for `source`. False for unknown nodes.
+    pub fn track_shortest_paths(&mut self, source: K) -> bool
+    {
+        let src = match self.graph.get_node_index(&source)
+        {
+            Some(i) => i,
+            None => return false
+        };
+        let (distance, parent) = self.graph.view().dijkstra(src, None);
+        self.trees.insert(source, SourceTree { distance, parent });
+        true
+    }
+
+    pub fn untrack_shortest_paths(&mut self, source: K) -> bool
+    {
+        self.trees.remove(&source).is_some()
+    }
+
+    // None when `source` isn't tracked or `to` can't be reached from it
+    pub fn cached_shortest_path(&self, source: K, to: K) -> Option<(W, Vec<K>)>
+    {
+        let tree = self.trees.get(&source)?;
+        let dst = self.graph.get_node_index(&to)?;
+        match tree.distance[dst]
+        {
+            TentativeWeight::Number(n) =>
+                Some((n, build_path(&self.graph.nodes, &tree.parent, dst))),
+            _ => None
+        }
+    }
+
+    pub fn add_node(&mut self, node: K) -> bool
+    {
+        if !self.graph.add_node(node.clone())
+        {
+            return false;
+        }
+        for tree in self.trees.values_mut()
+        {
+            tree.distance.push(TentativeWeight::Infinite);
+            tree.parent.push(None);
+        }
+        self.emit(GraphEvent::NodeAdded(node));
+        true
+    }
+
+    pub fn remove_node(&mut self, node: K) -> bool
+    {
+        let removed = match self.graph.get_node_index(&node)
+        {
+            Some(i) => i,
+            None => return false
+        };
+        let events: Vec<GraphEvent<K, W>> = self.graph.edge_list()
+            .filter(|(from, e)| *from == removed || e.node == removed)
+            .map(|(from, e)| self.edge_event(from, e.node, e.weight, false))
+            .collect();
+        self.graph.remove_node(node.clone());
+
+        self.trees.remove(&node);
+        let graph = &self.graph;
+        for tree in self.trees.values_mut()
+        {
+            tree.distance.remove(removed);
+            tree.parent.remove(removed);
+            let mut orphans = vec![];
+            for (i, p) in tree.parent.iter_mut().enumerate()
+            {
+                match *p
+                {
+                    Some(q) if q == removed =>
+                    {
+                        *p = None;
+                        orphans.push(i);
+                    }
+                    Some(q) if q > removed => *p = Some(q - 1),
+                    _ => {}
+                }
+            }
+            tree.invalidate(graph, orphans);
+        }
+
+        for event in events
+        {
+            self.emit(event);
+        }
+        self.emit(GraphEvent::NodeRemoved(node));
+        true
+    }
+
+    pub fn set_edges(&mut self, from: K, edges: Vec<(W, K)>)
+    {
+        if !self.graph.contains_node(from.clone())
+        {
+            self.add_node(from.clone());
+        }
+        let src = self.graph.index[&from];
+        let old: Vec<(usize, W)> = self.graph.adjacency_list[src].iter()
+            .map(|e| (e.node, e.weight))
+            .collect();
+        self.graph.set_edges(from, edges);
+        let new: Vec<(usize, W)> = self.graph.adjacency_list[src].iter()
+            .map(|e| (e.node, e.weight))
+            .collect();
+
+        for (to, _) in old.iter()
+        {
+            self.raised(src, *to);
+        }
+        for (to, weight) in new.iter()
+        {
+            self.lowered(src, *to, *weight);
+        }
+
+        for (to, weight) in old
+        {
+            let event = self.edge_event(src, to, weight, false);
+            self.emit(event);
+        }
+        for (to, weight) in new
+        {
+            let event = self.edge_event(src, to, weight, true);
+            self.emit(event);
+        }
+    }
+
+    pub fn add_edge(&mut self, from: K, to: K, weight: W) -> Result<(), UnknownNodes<K>>
+    {
+        let known = (self.graph.get_node_index(&from), self.graph.get_node_index(&to));
+        if let (Some(a), Some(b)) = known
+        {
+            // Simple graphs keep a single edge and just take the new weight
+            let simple = self.graph.mode.multiplicity == Multiplicity::Simple;
+            if simple && self.graph.adjacency_list[a].iter().any(|e| e.node == b)
+            {
+                self.update_weight(from, to, weight);
+                return Ok(());
+            }
+        }
+
+        self.graph.add_edge(from.clone(), to.clone(), weight)?;
+        let (a, b) = (self.graph.index[&from], self.graph.index[&to]);
+        self.lowered(a, b, weight);
+        self.emit(GraphEvent::EdgeAdded { from, to, weight });
+        Ok(())
+    }
+
+    pub fn remove_edge(&mut self, from: K, to: K) -> Option<W>
+    {
+        let weight = self.graph.remove_edge(from.clone(), to.clone())?;
+        let (a, b) = (self.graph.index[&from], self.graph.index[&to]);
+        self.raised(a, b);
+        self.emit(GraphEvent::EdgeRemoved { from, to, weight });
+        Some(weight)
+    }
+
+    pub fn update_weight(&mut self, from: K, to: K, weight: W) -> Option<W>
+    {
+        let old = self.graph.update_weight(from.clone(), to.clone(), weight)?;
+        let (a, b) = (self.graph.index[&from], self.graph.index[&to]);
+        if weight < old
+        {
+            self.lowered(a, b, weight);
+        } else if weight > old
+        {
+            self.raised(a, b);
+        }
+        self.emit(GraphEvent::EdgeReweighted { from, to, old, new: weight });
+        Some(old)
+    }
+
+    fn emit(&mut self, event: GraphEvent<K, W>)
+    {
+        for (_, observer) in self.observers.iter_mut()
+        {
+            observer(&event);
+        }
+    }
+
+    fn edge_event(&self, from: usize, to: usize, weight: W, added: bool) -> GraphEvent<K, W>
+    {
+        let (from, to) = (self.graph.nodes[from].clone(), self.graph.nodes[to].clone());
+        match added
+        {
+            true => GraphEvent::EdgeAdded { from, to, weight },
+            false => GraphEvent::EdgeRemoved { from, to, weight }
+        }
+    }
+
+    // An edge from -> to got cheaper or was added
+    fn lowered(&mut self, from: usize, to: usize, weight: W)
+    {
+        let undirected = !self.graph.mode.is_directed();
+        for tree in self.trees.values_mut()
+        {
+            tree.relax(&self.graph, from, to, weight);
+            if undirected
+            {
+                tree.relax(&self.graph, to, from, weight);
+            }
+        }
+    }
+
+    // An edge from -> to got dearer or was removed
+    fn raised(&mut self, from: usize, to: usize)
+    {
+        let undirected = !self.graph.mode.is_directed();
+        for tree in self.trees.values_mut()
+        {
+            let mut roots = vec![];
+            if tree.parent[to] == Some(from)
+            {
+                roots.push(to);
+            }
+            if undirected && tree.parent[from] == Some(to)
+            {
+                roots.push(from);
+            }
+            tree.invalidate(&self.graph, roots);
+        }
+    }
+}
+
+impl<W: NonNegative> SourceTree<W>
+{
+    fn relax<K>(&mut self, graph: &Graph<K, W>, from: usize, to: usize, weight: W)
+    {
+        let candidate = self.distance[from].add(weight);
+        if candidate < self.distance[to]
+        {
+            self.distance[to] = candidate.clone();
+            self.parent[to] = Some(from);
+            let mut open = BinaryHeap::new();
+            open.push(Reverse((candidate, to)));
+            self.settle(graph, open);
+        }
+    }
+
+    // Forgets everything hanging below `roots` in the tree, then finds new
+    // routes into that part from the rest of the tree
+    fn invalidate<K>(&mut self, graph: &Graph<K, W>, roots: Vec<usize>)
+    {
+        if roots.is_empty()
+        {
+            return;
+        }
+        let n = self.parent.len();
+        let mut children = vec![vec![]; n];
+        for (i, p) in self.parent.iter().enumerate()
+        {
+            if let Some(p) = p
+            {
+                children[*p].push(i);
+            }
+        }
+        let mut detached = vec![false; n];
+        let mut stack = roots;
+        while let Some(u) = stack.pop()
+        {
+            if detached[u]
+            {
+                continue;
+            }
+            detached[u] = true;
+            self.distance[u] = TentativeWeight::Infinite;
+            self.parent[u] = None;
+            stack.extend(children[u].iter().copied());
+        }
+
+        let mut open = BinaryHeap::new();
+        for (from, edges) in graph.adjacency_list.iter().enumerate()
+        {
+            if detached[from]
+            {
+                continue;
+            }
+            for e in edges.iter().filter(|e| detached[e.node])
+            {
+                let candidate = self.distance[from].add(e.weight);
+                if candidate < self.distance[e.node]
+                {
+                    self.distance[e.node] = candidate.clone();
+                    self.parent[e.node] = Some(from);
+                    open.push(Reverse((candidate, e.node)));
+                }
+            }
+        }
+        self.settle(graph, open);
+    }
+
+    // Dijkstra from whatever is in the heap, only ever lowering distances
+    fn settle<K>(&mut self, graph: &Graph<K, W>,
+        mut open: BinaryHeap<Reverse<(TentativeWeight<W>, usize)>>)
+    {
+        while let Some(Reverse((dist, u))) = open.pop()
+        {
+            if dist > self.distance[u]
+            {
+                continue;
+            }
+            for e in graph.adjacency_list[u].iter()
+            {
+                let candidate = dist.add(e.weight);
+                if candidate < self.distance[e.node]
+                {
+                    self.distance[e.node] = candidate.clone();
+                    self.parent[e.node] = Some(u);
+                    open.push(Reverse((candidate, e.node)));
+                }
+            }
+        }
+    }
+}
+
+impl<K: Hash + Eq + Clone, W: NonNegative> From<Graph<K, W>> for ObservedGraph<K, W>
+{
+    fn from(graph: Graph<K, W>) -> ObservedGraph<K, W>
+    {
+        ObservedGraph::new(graph)
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::test_support::{random_graph, random_undirected, Lcg};
+    use crate::{Direction, GraphMode, InternetOfThings, KeyType};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    type Events = Rc<RefCell<Vec<GraphEvent<KeyType, u32>>>>;
+
+    fn recorded(g: &mut ObservedGraph<KeyType, u32>) -> Events
+    {
+        let events = Rc::new(RefCell::new(vec![]));
+        let sink = Rc::clone(&events);
+        g.subscribe(move |e| sink.borrow_mut().push(e.clone()));
+        events
+    }
+
+    fn assert_trees_fresh(g: &ObservedGraph<KeyType, u32>, sources: &[KeyType])
+    {
+        for source in sources
+        {
+            if !g.graph().contains_node(*source)
+            {
+                continue;
+            }
+            for (to, (cost, _)) in g.graph().shortest_paths_from(*source)
+            {
+                let (cached, path) = g.cached_shortest_path(*source, to).unwrap();
+                assert_eq!(cached, cost, "{} -> {}", source, to);
+                assert_eq!(path.first(), Some(source));
+                assert_eq!(path.last(), Some(&to));
+            }
+            for to in 0..100
+            {
+                if g.graph().shorted_path(*source, to * 10).is_none()
+                {
+                    assert_eq!(g.cached_shortest_path(*source, to * 10), None);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn events_are_emitted()
+    {
+        let mut g = ObservedGraph::new(InternetOfThings::new());
+        let events = recorded(&mut g);
+        g.add_node(1);
+        g.add_node(1);
+        g.add_node(2);
+        g.add_edge(1, 2, 5).unwrap();
+        assert!(g.add_edge(1, 3, 5).is_err());
+        g.update_weight(1, 2, 7);
+        g.set_edges(2, vec![(1, 1)]);
+        g.remove_edge(1, 2);
+        g.remove_node(1);
+
+        use GraphEvent::*;
+        assert_eq!(
+            *events.borrow(),
+            vec![
+                NodeAdded(1),
+                NodeAdded(2),
+                EdgeAdded { from: 1, to: 2, weight: 5 },
+                EdgeReweighted { from: 1, to: 2, old: 5, new: 7 },
+                EdgeAdded { from: 2, to: 1, weight: 1 },
+                EdgeRemoved { from: 1, to: 2, weight: 7 },
+                EdgeRemoved { from: 2, to: 1, weight: 1 },
+                NodeRemoved(1),
+            ]
+        );
+    }
+
+    #[test]
+    fn unsubscribe_stops_events()
+    {
+        let mut g = ObservedGraph::new(InternetOfThings::new());
+        let events = recorded(&mut g);
+        let other = g.subscribe(|_| {});
+        g.add_node(1);
+        assert!(g.unsubscribe(other));
+        assert!(!g.unsubscribe(other));
+        assert!(g.unsubscribe(ObserverId(0)));
+        g.add_node(2);
+        assert_eq!(*events.borrow(), vec![GraphEvent::NodeAdded(1)]);
+    }
+
+    #[test]
+    fn simple_graphs_report_reweighting()
+    {
+        let mode = GraphMode::new(Direction::Undirected, Multiplicity::Simple);
+        let mut g = ObservedGraph::new(InternetOfThings::with_mode(mode));
+        g.add_node(1);
+        g.add_node(2);
+        let events = recorded(&mut g);
+        g.add_edge(1, 2, 4).unwrap();
+        g.add_edge(2, 1, 3).unwrap();
+        assert_eq!(
+            *events.borrow(),
+            vec![
+                GraphEvent::EdgeAdded { from: 1, to: 2, weight: 4 },
+                GraphEvent::EdgeReweighted { from: 2, to: 1, old: 4, new: 3 },
+            ]
+        );
+        assert_eq!(g.graph().edges(), 1);
+    }
+
+    #[test]
+    fn cached_tree_follows_edits()
+    {
+        let mut g = ObservedGraph::new(InternetOfThings::new());
+        for n in 1..=4
+        {
+            g.add_node(n);
+        }
+        g.set_edges(1, vec![(1, 2), (10, 4)]);
+        g.set_edges(2, vec![(1, 3)]);
+        g.set_edges(3, vec![(1, 4)]);
+        assert!(g.track_shortest_paths(1));
+        assert!(!g.track_shortest_paths(9));
+        assert_eq!(g.cached_shortest_path(1, 4), Some((3, vec![1, 2, 3, 4])));
+
+        g.update_weight(2, 3, 20);
+        assert_eq!(g.cached_shortest_path(1, 4), Some((10, vec![1, 4])));
+        g.remove_edge(1, 4);
+        assert_eq!(g.cached_shortest_path(1, 4), Some((22, vec![1, 2, 3, 4])));
+        g.remove_node(3);
+        assert_eq!(g.cached_shortest_path(1, 4), None);
+        g.add_edge(2, 4, 2).unwrap();
+        assert_eq!(g.cached_shortest_path(1, 4), Some((3, vec![1, 2, 4])));
+        assert_eq!(g.cached_shortest_path(2, 4), None);
+
+        assert!(g.untrack_shortest_paths(1));
+        assert_eq!(g.cached_shortest_path(1, 4), None);
+    }
+
+    #[test]
+    fn random_edits_match_recomputation()
+    {
+        let mut rng = Lcg(17);
+        for round in 0..6
+        {
+            let base = match round % 2
+            {
+                0 => random_graph(&mut rng, 60, 3),
+                _ => random_undirected(&mut rng, 60, 120, 50)
+            };
+            let mut g = ObservedGraph::new(base);
+            let sources = [0, 70, 330];
+            for s in sources.iter()
+            {
+                g.track_shortest_paths(*s);
+            }
+
+            for _ in 0..150
+            {
+                let (a, b) = (rng.key(60), rng.key(60));
+                let w = 1 + rng.below(100) as u32;
+                match rng.below(6)
+                {
+                    0 | 1 =>
+                    {
+                        let _ = g.add_edge(a, b, w);
+                    }
+                    2 =>
+                    {
+                        g.remove_edge(a, b);
+                    }
+                    3 =>
+                    {
+                        g.update_weight(a, b, w);
+                    }
+                    4 =>
+                    {
+                        g.set_edges(a, vec![(w, b), (w / 2 + 1, (b + 10) % 600)]);
+                    }
+                    _ =>
+                    {
+                        if rng.below(4) == 0
+                        {
+                            // Removing a source drops its tree
+                            g.remove_node(a);
+                            g.add_node(a);
+                            if sources.contains(&a)
+                            {
+                                g.track_shortest_paths(a);
+                            }
+                        }
+                    }
+                }
+                assert_trees_fresh(&g, &sources);
+            }
+        }
+    }
+}
@@ -0,0 +1,258 @@
+use std::collections::VecDeque;
+use std::hash::Hash;
+
+use super::traversal::{forward_to_view, GraphView};
+use super::KeyType;
+
+// Result of a max-flow computation. `flows` has one entry per edge of the
+// graph carrying flow, `min_cut` the saturated edges separating both sides.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MaxFlow<K = KeyType>
+{
+    pub value: u64,
+    pub flows: Vec<(K, K, u32)>,
+    pub min_cut: Vec<(K, K, u32)>
+}
+
+struct Arc
+{
+    to: usize,
+    capacity: u64,
+    // Index of the opposite arc in `to`'s list
+    reverse: usize
+}
+
+forward_to_view!
+{
+    [K: Hash + Eq + Clone] (K, u32)
+    fn max_flow(&self, source: K, sink: K) -> Option<MaxFlow<K>>;
+}
+
+impl<'a, K: Hash + Eq + Clone> GraphView<'a, K, u32>
+{
+    // Dinic's algorithm, using `Edge::weight` as the capacity of each link
+    pub(crate) fn max_flow(&self, source: K, sink: K) -> Option<MaxFlow<K>>
+    {
+        let src = self.get_node_index(&source)?;
+        let dst = self.get_node_index(&sink)?;
+        if src == dst
+        {
+            return None;
+        }
+
+        let mut residual: Vec<Vec<Arc>> = (0..self.nodes.len()).map(|_| vec![]).collect();
+        // Position of every original edge's forward arc, in adjacency list order
+        let mut forward = vec![];
+        for from in 0..self.nodes.len()
+        {
+            for e in self.adjacent(from)
+            {
+                // A self loop puts both arcs into the same list
+                let a = residual[from].len();
+                let b = residual[e.node].len() + (from == e.node) as usize;
+                residual[from].push(Arc { to: e.node, capacity: e.weight as u64, reverse: b });
+                residual[e.node].push(Arc { to: from, capacity: 0, reverse: a });
+                forward.push((from, a));
+            }
+        }
+
+        let mut value = 0u64;
+        while let Some(level) = levels(&residual, src, dst)
+        {
+            value += blocking_flow(&mut residual, &level, src, dst);
+        }
+
+        let mut flows = vec![];
+        let mut edges = forward.iter();
+        for (from, arcs) in residual.iter().enumerate()
+        {
+            for e in self.adjacent(from)
+            {
+                let (_, a) = edges.next().unwrap();
+                let flow = e.weight as u64 - arcs[*a].capacity;
+                if flow > 0
+                {
+                    flows.push((self.nodes[from].clone(), self.nodes[e.node].clone(), flow as u32));
+                }
+            }
+        }
+
+        // Whatever the source can still reach forms one side of the minimum cut
+        let reachable = reachable_from(&residual, src);
+        let mut min_cut = vec![];
+        for from in 0..self.nodes.len()
+        {
+            for e in self.adjacent(from)
+            {
+                let crosses = reachable[from] != usize::MAX && reachable[e.node] == usize::MAX;
+                if crosses && e.weight > 0
+                {
+                    min_cut.push((self.nodes[from].clone(), self.nodes[e.node].clone(), e.weight));
+                }
+            }
+        }
+
+        Some(MaxFlow { value, flows, min_cut })
+    }
+}
+
+// BFS levels over arcs with spare capacity, None when the sink can't be reached.
+// Unreached nodes are left at usize::MAX.
+fn levels(residual: &[Vec<Arc>], src: usize, dst: usize) -> Option<Vec<usize>>
+{
+    let level = reachable_from(residual, src);
+    if level[dst] != usize::MAX
+    {
+        Some(level)
+    } else {
+        None
+    }
+}
+
+fn reachable_from(residual: &[Vec<Arc>], src: usize) -> Vec<usize>
+{
+    let mut level = vec![usize::MAX; residual.len()];
+    level[src] = 0;
+    let mut queue: VecDeque<usize> = vec![src].into_iter().collect();
+    while let Some(u) = queue.pop_front()
+    {
+        for arc in &residual[u]
+        {
+            if arc.capacity > 0 && level[arc.to] == usize::MAX
+            {
+                level[arc.to] = level[u] + 1;
+                queue.push_back(arc.to);
+            }
+        }
+    }
+    level
+}
+
+// Pushes augmenting paths through the level graph until none are left.
+// The depth-first search keeps its own stack so long paths can't overflow.
+fn blocking_flow(residual: &mut [Vec<Arc>], level: &[usize], src: usize, dst: usize) -> u64
+{
+    let mut next_arc = vec![0; residual.len()];
+    let mut dead = vec![false; residual.len()];
+    let mut total = 0;
+
+    loop
+    {
+        let mut path: Vec<(usize, usize)> = vec![];
+        let mut u = src;
+        while u != dst
+        {
+            let found = loop
+            {
+                match residual[u].get(next_arc[u])
+                {
+                    Some(arc) if arc.capacity > 0 && !dead[arc.to]
+                        && level[arc.to] == level[u].wrapping_add(1) => break true,
+                    Some(_) => next_arc[u] += 1,
+                    None => break false
+                }
+            };
+
+            if found
+            {
+                path.push((u, next_arc[u]));
+                u = residual[u][next_arc[u]].to;
+            } else {
+                dead[u] = true;
+                match path.pop()
+                {
+                    Some((previous, arc)) =>
+                    {
+                        next_arc[previous] = arc + 1;
+                        u = previous;
+                    },
+                    None => return total
+                }
+            }
+        }
+
+        let bottleneck = path.iter().map(|(n, a)| residual[*n][*a].capacity).min().unwrap();
+        for (n, a) in path
+        {
+            residual[n][a].capacity -= bottleneck;
+            let (to, reverse) = (residual[n][a].to, residual[n][a].reverse);
+            residual[to][reverse].capacity += bottleneck;
+        }
+        total += bottleneck;
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::InternetOfThings;
+
+    #[test]
+    fn classic_network()
+    {
+        let mut g = InternetOfThings::new();
+        g.set_nodes(vec![1, 2, 3, 4, 5, 6]);
+        g.set_edges(1, vec![(16, 2), (13, 3)]);
+        g.set_edges(2, vec![(12, 4), (10, 3)]);
+        g.set_edges(3, vec![(4, 2), (14, 5)]);
+        g.set_edges(4, vec![(9, 3), (20, 6)]);
+        g.set_edges(5, vec![(7, 4), (4, 6)]);
+
+        let result = g.max_flow(1, 6).unwrap();
+        assert_eq!(result.value, 23);
+        assert_eq!(result.min_cut.iter().map(|e| e.2 as u64).sum::<u64>(), 23);
+
+        let total = |keep: &dyn Fn(&(KeyType, KeyType, u32)) -> bool| -> u64
+        {
+            result.flows.iter().filter(|f| keep(f)).map(|f| f.2 as u64).sum()
+        };
+        // Flow is conserved at every inner node
+        for node in 2..6
+        {
+            assert_eq!(total(&|f| f.1 == node), total(&|f| f.0 == node));
+        }
+        assert_eq!(total(&|f| f.0 == 1), 23);
+    }
+
+    #[test]
+    fn bottleneck_is_the_cut()
+    {
+        let mut g = InternetOfThings::new();
+        g.set_nodes(vec![1, 2, 3, 4]);
+        g.set_edges(1, vec![(10, 2), (10, 3)]);
+        g.set_edges(2, vec![(3, 4)]);
+        g.set_edges(3, vec![(2, 4)]);
+        let result = g.max_flow(1, 4).unwrap();
+        assert_eq!(result.value, 5);
+        let mut cut = result.min_cut.clone();
+        cut.sort();
+        assert_eq!(cut, vec![(2, 4, 3), (3, 4, 2)]);
+    }
+
+    #[test]
+    fn unreachable_sink_and_unknown_nodes()
+    {
+        let mut g = InternetOfThings::new();
+        g.set_nodes(vec![1, 2, 3]);
+        g.set_edges(1, vec![(5, 2)]);
+        let result = g.max_flow(1, 3).unwrap();
+        assert_eq!(result.value, 0);
+        assert!(result.flows.is_empty());
+        assert!(result.min_cut.is_empty());
+        assert!(g.max_flow(1, 9).is_none());
+        assert!(g.max_flow(1, 1).is_none());
+    }
+
+    #[test]
+    fn long_chain()
+    {
+        let mut g = InternetOfThings::new();
+        g.set_nodes((0..50_000).collect());
+        for n in 0..49_999
+        {
+            g.set_edges(n, vec![(7, n + 1)]);
+        }
+        assert_eq!(g.max_flow(0, 49_999).unwrap().value, 7);
+    }
+}
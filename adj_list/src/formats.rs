@@ -0,0 +1,1190 @@
+use std::error::Error;
+use std::fmt;
+use std::fmt::{Display, Write};
+use std::hash::Hash;
+use std::iter::Peekable;
+use std::str::FromStr;
+
+use super::{Direction, Graph, GraphMode, Multiplicity};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseErrorKind
+{
+    // The line doesn't follow the format at all
+    Malformed(String),
+    InvalidKey(String),
+    InvalidWeight(String),
+    // An edge refers to a node that was never declared
+    UnknownNode(String)
+}
+
+// Line numbers start at 1
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError
+{
+    pub line: usize,
+    pub kind: ParseErrorKind
+}
+
+impl ParseError
+{
+    fn new(line: usize, kind: ParseErrorKind) -> ParseError
+    {
+        ParseError { line, kind }
+    }
+}
+
+impl fmt::Display for ParseError
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        match &self.kind
+        {
+            ParseErrorKind::Malformed(m) => write!(f, "Line {}: {}", self.line, m),
+            ParseErrorKind::InvalidKey(k) => write!(f, "Line {}: invalid node '{}'", self.line, k),
+            ParseErrorKind::InvalidWeight(w) =>
+                write!(f, "Line {}: invalid weight '{}'", self.line, w),
+            ParseErrorKind::UnknownNode(n) => write!(f, "Line {}: unknown node '{}'", self.line, n)
+        }
+    }
+}
+
+impl Error for ParseError {}
+
+impl<K, W> Graph<K, W>
+where
+    K: Hash + Eq + Clone + FromStr + Display,
+    W: Clone + FromStr + Display
+{
+    // Rows are `from,to,weight` with fields quoted as in RFC 4180. A row with
+    // a single field declares a node without edges and an optional
+    // `from,to,weight` header is skipped. A leading `# mode: <direction>
+    // <multiplicity>` line picks the graph's mode, directed multi otherwise.
+    // Nodes are created as they show up.
+    pub fn from_edge_list_csv(input: &str) -> Result<Graph<K, W>, ParseError>
+    {
+        let (mode, records) = csv_document(input)?;
+        let mut graph = Graph::with_mode(mode.map(|(_, m)| m).unwrap_or_default());
+        graph.read_edge_list_csv(records, true)?;
+        Ok(graph)
+    }
+
+    // Adds the edges in the CSV to the nodes already in the graph; rows naming
+    // any other node are rejected, as is a mode line that doesn't match.
+    pub fn load_edge_list_csv(&mut self, input: &str) -> Result<(), ParseError>
+    {
+        let (mode, records) = csv_document(input)?;
+        if let Some((line, mode)) = mode
+        {
+            if mode != self.mode
+            {
+                return Err(ParseError::new(line, ParseErrorKind::Malformed(format!(
+                    "the file is for a {} graph, this one is {}",
+                    mode_name(mode),
+                    mode_name(self.mode)
+                ))));
+            }
+        }
+        self.read_edge_list_csv(records, false)
+    }
+
+    pub fn to_edge_list_csv(&self) -> String
+    {
+        let mut out = format!("# mode: {}\nfrom,to,weight\n", mode_name(self.mode));
+        let mut has_edges = vec![false; self.nodes.len()];
+        for (from, e) in self.edge_list()
+        {
+            has_edges[from] = true;
+            has_edges[e.node] = true;
+            writeln!(
+                out,
+                "{},{},{}",
+                csv_quote(&self.nodes[from].to_string()),
+                csv_quote(&self.nodes[e.node].to_string()),
+                csv_quote(&e.weight.to_string())
+            ).unwrap();
+        }
+        for (i, n) in self.nodes.iter().enumerate()
+        {
+            if !has_edges[i]
+            {
+                writeln!(out, "{}", csv_quote(&n.to_string())).unwrap();
+            }
+        }
+        out
+    }
+
+    // Understands the subset of Graphviz DOT that `to_dot` writes: a single
+    // `digraph` (or undirected `graph`) holding node and edge statements,
+    // separated by `;` or simply following one another. The weight comes from
+    // the `weight` attribute, or `label` when there is none.
+    pub fn from_dot(input: &str) -> Result<Graph<K, W>, ParseError>
+    {
+        let mut tokens = vec![];
+        for (i, line) in input.lines().enumerate()
+        {
+            let line_tokens = dot_tokens(line)
+                .map_err(|m| ParseError::new(i + 1, ParseErrorKind::Malformed(m)))?;
+            tokens.extend(line_tokens.into_iter().map(|t| (i + 1, t)));
+        }
+        let mut tokens = tokens.into_iter().peekable();
+
+        let mut header = vec![];
+        let mut header_line = 1;
+        for (line, token) in tokens.by_ref()
+        {
+            header_line = line;
+            let done = token == DotToken::OpenBrace;
+            header.push(token);
+            if done || header.len() == 3
+            {
+                break;
+            }
+        }
+        let kind = match header.as_slice()
+        {
+            [DotToken::Id(g), DotToken::OpenBrace] => g.as_str(),
+            [DotToken::Id(g), DotToken::Id(_), DotToken::OpenBrace] => g.as_str(),
+            _ => ""
+        };
+        let (mut graph, edge_op) = match kind
+        {
+            "digraph" => (Graph::new(), DotToken::Arrow),
+            "graph" =>
+            {
+                let mode = GraphMode::new(Direction::Undirected, Multiplicity::Multi);
+                (Graph::with_mode(mode), DotToken::Line)
+            },
+            _ => return Err(ParseError::new(header_line, ParseErrorKind::Malformed(
+                "expected 'digraph {' or 'graph {'".into())))
+        };
+
+        let mut edges = vec![];
+        let mut closed = false;
+        while let Some((line_no, token)) = tokens.next()
+        {
+            let malformed = |m: &str| ParseError::new(line_no, ParseErrorKind::Malformed(m.into()));
+            match token
+            {
+                DotToken::CloseBrace =>
+                {
+                    closed = true;
+                    break;
+                },
+                DotToken::Separator => continue,
+                _ => {}
+            }
+
+            let statement = dot_statement(token, &mut tokens);
+            let statement = parse_dot_statement(&statement, &edge_op)
+                .map_err(|m| malformed(&m))?;
+            let (ids, attributes) = match statement
+            {
+                Some(s) => s,
+                None => continue
+            };
+
+            let mut keys = vec![];
+            for id in ids.iter()
+            {
+                let key: K = id.parse().map_err(|_|
+                {
+                    ParseError::new(line_no, ParseErrorKind::InvalidKey(id.clone()))
+                })?;
+                graph.add_node(key.clone());
+                keys.push(key);
+            }
+            if keys.len() > 1
+            {
+                let weight = attributes.iter()
+                    .find(|(k, _)| k == "weight")
+                    .or_else(|| attributes.iter().find(|(k, _)| k == "label"))
+                    .map(|(_, v)| v)
+                    .ok_or_else(|| malformed("edge without a weight"))?;
+                let weight: W = weight.parse().map_err(|_|
+                {
+                    ParseError::new(line_no, ParseErrorKind::InvalidWeight(weight.clone()))
+                })?;
+                for pair in keys.windows(2)
+                {
+                    edges.push((pair[0].clone(), pair[1].clone(), weight.clone()));
+                }
+            }
+        }
+
+        if !closed
+        {
+            let last = input.lines().count().max(1);
+            let kind = ParseErrorKind::Malformed("missing closing brace".into());
+            return Err(ParseError::new(last, kind));
+        }
+        if let Some((line, _)) = tokens.next()
+        {
+            let kind = ParseErrorKind::Malformed("content after the closing brace".into());
+            return Err(ParseError::new(line, kind));
+        }
+
+        for (from, to, weight) in edges
+        {
+            graph.push_edge(&from, &to, weight);
+        }
+        Ok(graph)
+    }
+
+    pub fn to_dot(&self) -> String
+    {
+        let (kind, edge_op) = if self.mode.is_directed()
+        {
+            ("digraph", "->")
+        } else {
+            ("graph", "--")
+        };
+        let mut out = format!("{} {{\n", kind);
+        for n in self.nodes.iter()
+        {
+            writeln!(out, "    {};", dot_quote(&n.to_string())).unwrap();
+        }
+        for (from, e) in self.edge_list()
+        {
+            writeln!(
+                out,
+                "    {} {} {} [weight={}];",
+                dot_quote(&self.nodes[from].to_string()),
+                edge_op,
+                dot_quote(&self.nodes[e.node].to_string()),
+                dot_quote(&e.weight.to_string())
+            ).unwrap();
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    // Reads `<node id>` and `<edge source target>` elements with the weight in
+    // a `<data>` child whose key is declared for edges with `attr.name="weight"`.
+    pub fn from_graphml(input: &str) -> Result<Graph<K, W>, ParseError>
+    {
+        let mut weight_key = String::from("weight");
+        let mut direction = Direction::Directed;
+        let mut nodes = vec![];
+        let mut edges: Vec<(usize, String, String, Option<String>)> = vec![];
+        let mut in_edge = false;
+        let mut in_weight = false;
+
+        for token in xml_tokens(input)?
+        {
+            match token
+            {
+                XmlToken::Open { line, name, attributes, closed } =>
+                {
+                    let attribute = |n: &str| attributes.iter()
+                        .find(|(k, _)| k == n)
+                        .map(|(_, v)| v.clone());
+                    let required = |n: &str| attribute(n).ok_or_else(||
+                    {
+                        ParseError::new(line, ParseErrorKind::Malformed(
+                            format!("<{}> without a {} attribute", name, n)))
+                    });
+
+                    match name.as_str()
+                    {
+                        // A key without `for` applies to everything
+                        "key" if attribute("attr.name").as_deref() == Some("weight")
+                            && matches!(attribute("for").as_deref(), None | Some("edge" | "all")) =>
+                            weight_key = required("id")?,
+                        "graph" if attribute("edgedefault").as_deref() == Some("undirected") =>
+                            direction = Direction::Undirected,
+                        "node" => nodes.push((line, required("id")?)),
+                        "edge" =>
+                        {
+                            edges.push((line, required("source")?, required("target")?, None));
+                            in_edge = !closed;
+                        },
+                        "data" if in_edge && attribute("key") == Some(weight_key.clone()) =>
+                        {
+                            in_weight = !closed;
+                        },
+                        _ => {}
+                    }
+                },
+                XmlToken::Close { name } => match name.as_str()
+                {
+                    "edge" => in_edge = false,
+                    "data" => in_weight = false,
+                    _ => {}
+                },
+                XmlToken::Text { text } =>
+                {
+                    if in_weight
+                    {
+                        if let Some(edge) = edges.last_mut()
+                        {
+                            edge.3 = Some(text.trim().to_string());
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut graph = Graph::with_mode(GraphMode::new(direction, Multiplicity::Multi));
+        for (line, id) in nodes
+        {
+            let key: K = id.parse().map_err(|_|
+            {
+                ParseError::new(line, ParseErrorKind::InvalidKey(id.clone()))
+            })?;
+            graph.add_node(key);
+        }
+        for (line, source, target, weight) in edges
+        {
+            let from = graph.parse_known_node(line, &source)?;
+            let to = graph.parse_known_node(line, &target)?;
+            let weight = weight.ok_or_else(||
+            {
+                ParseError::new(line, ParseErrorKind::Malformed("edge without a weight".into()))
+            })?;
+            let weight: W = weight.parse().map_err(|_|
+            {
+                ParseError::new(line, ParseErrorKind::InvalidWeight(weight.clone()))
+            })?;
+            graph.push_edge(&from, &to, weight);
+        }
+        Ok(graph)
+    }
+
+    pub fn to_graphml(&self) -> String
+    {
+        let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        out.push_str("<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n");
+        out.push_str("  <key id=\"weight\" for=\"edge\" attr.name=\"weight\" ");
+        out.push_str("attr.type=\"string\"/>\n");
+        let direction = if self.mode.is_directed() { "directed" } else { "undirected" };
+        writeln!(out, "  <graph id=\"G\" edgedefault=\"{}\">", direction).unwrap();
+        for n in self.nodes.iter()
+        {
+            writeln!(out, "    <node id=\"{}\"/>", xml_escape(&n.to_string())).unwrap();
+        }
+        for (from, e) in self.edge_list()
+        {
+            writeln!(
+                out,
+                "    <edge source=\"{}\" target=\"{}\">",
+                xml_escape(&self.nodes[from].to_string()),
+                xml_escape(&self.nodes[e.node].to_string())
+            ).unwrap();
+            writeln!(
+                out,
+                "      <data key=\"weight\">{}</data>",
+                xml_escape(&e.weight.to_string())
+            ).unwrap();
+            out.push_str("    </edge>\n");
+        }
+        out.push_str("  </graph>\n</graphml>\n");
+        out
+    }
+
+    // Every row is checked before the graph changes, so a bad row leaves it
+    // exactly as it was
+    fn read_edge_list_csv(&mut self, records: Vec<CsvRecord>, create_nodes: bool)
+        -> Result<(), ParseError>
+    {
+        let mut rows: Vec<(K, Option<(K, W)>)> = vec![];
+        for (i, record) in records.into_iter().enumerate()
+        {
+            let line_no = record.line;
+            let fields: Vec<&str> = record.fields.iter().map(|f| f.as_str()).collect();
+            if i == 0 && !record.quoted && fields == ["from", "to", "weight"]
+            {
+                continue;
+            }
+
+            let node = |raw: &str| if create_nodes
+            {
+                parse_key::<K>(line_no, raw)
+            } else {
+                self.parse_known_node(line_no, raw)
+            };
+            match fields.as_slice()
+            {
+                [single] => rows.push((node(single)?, None)),
+                [from, to, weight] =>
+                {
+                    let weight: W = weight.parse().map_err(|_|
+                    {
+                        ParseError::new(line_no, ParseErrorKind::InvalidWeight(weight.to_string()))
+                    })?;
+                    rows.push((node(from)?, Some((node(to)?, weight))));
+                },
+                _ => return Err(ParseError::new(line_no, ParseErrorKind::Malformed(
+                    format!("expected 'from,to,weight', found {} fields", fields.len()))))
+            }
+        }
+
+        for (from, edge) in rows
+        {
+            if create_nodes
+            {
+                self.add_node(from.clone());
+            }
+            if let Some((to, weight)) = edge
+            {
+                if create_nodes
+                {
+                    self.add_node(to.clone());
+                }
+                self.push_edge(&from, &to, weight);
+            }
+        }
+        Ok(())
+    }
+
+    fn parse_known_node(&self, line: usize, raw: &str) -> Result<K, ParseError>
+    {
+        let key = parse_key::<K>(line, raw)?;
+        if self.index.contains_key(&key)
+        {
+            Ok(key)
+        } else {
+            Err(ParseError::new(line, ParseErrorKind::UnknownNode(raw.to_string())))
+        }
+    }
+
+    // Both nodes have to exist already
+    fn push_edge(&mut self, from: &K, to: &K, weight: W)
+    {
+        let (from, to) = (self.index[from], self.index[to]);
+        self.insert_edge(from, to, weight);
+    }
+}
+
+fn parse_key<K: FromStr>(line: usize, raw: &str) -> Result<K, ParseError>
+{
+    raw.parse().map_err(|_| ParseError::new(line, ParseErrorKind::InvalidKey(raw.to_string())))
+}
+
+fn mode_name(mode: GraphMode) -> String
+{
+    let direction = if mode.is_directed() { "directed" } else { "undirected" };
+    let multiplicity = match mode.multiplicity
+    {
+        Multiplicity::Simple => "simple",
+        Multiplicity::Multi => "multi"
+    };
+    format!("{} {}", direction, multiplicity)
+}
+
+fn parse_mode(text: &str) -> Option<GraphMode>
+{
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let direction = match words.first()
+    {
+        Some(&"directed") => Direction::Directed,
+        Some(&"undirected") => Direction::Undirected,
+        _ => return None
+    };
+    let multiplicity = match &words[1..]
+    {
+        ["simple"] => Multiplicity::Simple,
+        ["multi"] => Multiplicity::Multi,
+        _ => return None
+    };
+    Some(GraphMode::new(direction, multiplicity))
+}
+
+// One row of a CSV file, `line` being where it starts
+struct CsvRecord
+{
+    line: usize,
+    fields: Vec<String>,
+    // Whether any field was quoted, so `"from",to,weight` isn't taken for the header
+    quoted: bool
+}
+
+// The mode line, if there is one, and the records after it
+type CsvDocument = (Option<(usize, GraphMode)>, Vec<CsvRecord>);
+
+fn csv_document(input: &str) -> Result<CsvDocument, ParseError>
+{
+    let mut records = csv_records(input)?;
+    let mode_line = records.first()
+        .filter(|r| !r.quoted && r.fields.len() == 1)
+        .and_then(|r| r.fields[0].strip_prefix("# mode:").map(|m| (r.line, m.to_string())));
+
+    match mode_line
+    {
+        Some((line, text)) =>
+        {
+            let mode = parse_mode(&text).ok_or_else(||
+            {
+                ParseError::new(line, ParseErrorKind::Malformed(format!(
+                    "expected '# mode: <directed|undirected> <simple|multi>', found '{}'",
+                    text.trim()
+                )))
+            })?;
+            records.remove(0);
+            Ok((Some((line, mode)), records))
+        },
+        None => Ok((None, records))
+    }
+}
+
+// Splits the input into records following RFC 4180: fields in double quotes
+// may hold commas, line breaks and `""` for a quote. Unquoted fields are
+// trimmed and empty lines are skipped.
+fn csv_records(input: &str) -> Result<Vec<CsvRecord>, ParseError>
+{
+    let mut records = vec![];
+    let mut chars = input.chars().peekable();
+    let mut line = 1;
+
+    while chars.peek().is_some()
+    {
+        let start = line;
+        let mut fields = vec![];
+        let mut field = String::new();
+        let mut quoted = false;
+        let mut field_quoted = false;
+        let mut blank = true;
+        loop
+        {
+            match chars.next()
+            {
+                None | Some('\n') =>
+                {
+                    line += 1;
+                    break;
+                },
+                Some('\r') if chars.peek() == Some(&'\n') => {},
+                Some(',') =>
+                {
+                    fields.push(csv_field(field, field_quoted));
+                    field = String::new();
+                    field_quoted = false;
+                    blank = false;
+                },
+                Some('"') if !field_quoted && field.trim().is_empty() =>
+                {
+                    field.clear();
+                    field_quoted = true;
+                    quoted = true;
+                    blank = false;
+                    loop
+                    {
+                        match chars.next()
+                        {
+                            Some('"') if chars.peek() == Some(&'"') =>
+                            {
+                                chars.next();
+                                field.push('"');
+                            },
+                            Some('"') => break,
+                            Some(c) =>
+                            {
+                                if c == '\n'
+                                {
+                                    line += 1;
+                                }
+                                field.push(c);
+                            },
+                            None => return Err(ParseError::new(start, ParseErrorKind::Malformed(
+                                "unterminated quoted field".into())))
+                        }
+                    }
+                },
+                Some(c) if field_quoted =>
+                {
+                    if !c.is_whitespace()
+                    {
+                        return Err(ParseError::new(line, ParseErrorKind::Malformed(
+                            "text after a quoted field".into())));
+                    }
+                },
+                Some(c) =>
+                {
+                    blank = blank && c.is_whitespace();
+                    field.push(c);
+                }
+            }
+        }
+        if !blank
+        {
+            fields.push(csv_field(field, field_quoted));
+            records.push(CsvRecord { line: start, fields, quoted });
+        }
+    }
+    Ok(records)
+}
+
+fn csv_field(field: String, quoted: bool) -> String
+{
+    if quoted
+    {
+        field
+    } else {
+        field.trim().to_string()
+    }
+}
+
+// Quotes fields that wouldn't read back the same otherwise
+fn csv_quote(field: &str) -> String
+{
+    let plain = !field.is_empty()
+        && field.trim() == field
+        && !field.starts_with('#')
+        && !field.contains([',', '"', '\n', '\r']);
+    if plain
+    {
+        field.to_string()
+    } else {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum DotToken
+{
+    Id(String),
+    Arrow,
+    // `--` in undirected graphs
+    Line,
+    OpenBrace,
+    CloseBrace,
+    OpenBracket,
+    CloseBracket,
+    Equals,
+    Separator
+}
+
+fn dot_tokens(line: &str) -> Result<Vec<DotToken>, String>
+{
+    let mut tokens = vec![];
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next()
+    {
+        match c
+        {
+            '#' => break,
+            '/' if chars.peek() == Some(&'/') => break,
+            '-' if chars.peek() == Some(&'>') =>
+            {
+                chars.next();
+                tokens.push(DotToken::Arrow);
+            },
+            '-' if chars.peek() == Some(&'-') =>
+            {
+                chars.next();
+                tokens.push(DotToken::Line);
+            },
+            '{' => tokens.push(DotToken::OpenBrace),
+            '}' => tokens.push(DotToken::CloseBrace),
+            '[' => tokens.push(DotToken::OpenBracket),
+            ']' => tokens.push(DotToken::CloseBracket),
+            '=' => tokens.push(DotToken::Equals),
+            ';' | ',' => tokens.push(DotToken::Separator),
+            '"' =>
+            {
+                let mut id = String::new();
+                loop
+                {
+                    match chars.next()
+                    {
+                        Some('\\') => match chars.next()
+                        {
+                            Some(escaped) => id.push(escaped),
+                            None => return Err("unterminated string".into())
+                        },
+                        Some('"') => break,
+                        Some(c) => id.push(c),
+                        None => return Err("unterminated string".into())
+                    }
+                }
+                tokens.push(DotToken::Id(id));
+            },
+            c if c.is_whitespace() => {},
+            c if c.is_alphanumeric() || "_.:-".contains(c) =>
+            {
+                let mut id = c.to_string();
+                while let Some(&next) = chars.peek()
+                {
+                    if next.is_alphanumeric() || "_.:".contains(next)
+                        || (next == '-' && !matches!(chars.clone().nth(1), Some('>') | Some('-')))
+                    {
+                        id.push(next);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                tokens.push(DotToken::Id(id));
+            },
+            c => return Err(format!("unexpected character '{}'", c))
+        }
+    }
+    Ok(tokens)
+}
+
+// Takes the tokens of the statement starting with `first`. A statement ends
+// at a `;`, after its attribute list, before the closing brace or where an
+// id follows an id without an operator in between, as in `a -> b c -> d`.
+fn dot_statement<I>(first: DotToken, tokens: &mut Peekable<I>) -> Vec<DotToken>
+where
+    I: Iterator<Item = (usize, DotToken)>
+{
+    let mut statement = vec![first];
+    let mut in_attributes = statement[0] == DotToken::OpenBracket;
+    while let Some((_, next)) = tokens.peek()
+    {
+        if in_attributes
+        {
+            in_attributes = *next != DotToken::CloseBracket;
+        } else {
+            let previous = statement.last().unwrap();
+            let ends = match next
+            {
+                DotToken::Separator =>
+                {
+                    tokens.next();
+                    break;
+                },
+                DotToken::CloseBrace => true,
+                DotToken::Id(_) => matches!(previous, DotToken::Id(_) | DotToken::CloseBracket),
+                DotToken::OpenBracket => *previous == DotToken::CloseBracket,
+                _ => *previous == DotToken::CloseBracket
+            };
+            if ends
+            {
+                break;
+            }
+            in_attributes = *next == DotToken::OpenBracket;
+        }
+        statement.push(tokens.next().unwrap().1);
+    }
+    statement
+}
+
+// Node ids of a node or edge statement plus its attributes. Graph-wide
+// settings such as `rankdir=LR` or `node [shape=box]` yield None.
+type DotStatement = Option<(Vec<String>, Vec<(String, String)>)>;
+
+fn parse_dot_statement(tokens: &[DotToken], edge_op: &DotToken) -> Result<DotStatement, String>
+{
+    let mut tokens: Vec<&DotToken> = tokens.iter().collect();
+    while tokens.last() == Some(&&DotToken::Separator)
+    {
+        tokens.pop();
+    }
+
+    let split = tokens.iter().position(|t| *t == &DotToken::OpenBracket).unwrap_or(tokens.len());
+    let (head, tail) = tokens.split_at(split);
+
+    let mut attributes = vec![];
+    if !tail.is_empty()
+    {
+        if tail.last() != Some(&&DotToken::CloseBracket)
+        {
+            return Err("unterminated attribute list".into());
+        }
+        let list: Vec<&&DotToken> = tail[1..tail.len() - 1].iter()
+            .filter(|t| ***t != DotToken::Separator)
+            .collect();
+        for pair in list.chunks(3)
+        {
+            match pair
+            {
+                [DotToken::Id(k), DotToken::Equals, DotToken::Id(v)] =>
+                    attributes.push((k.clone(), v.clone())),
+                _ => return Err("expected key=value attributes".into())
+            }
+        }
+    }
+
+    match head
+    {
+        [DotToken::Id(k), ..] if ["graph", "node", "edge"].contains(&k.as_str()) => Ok(None),
+        [DotToken::Id(_), DotToken::Equals, DotToken::Id(_)] => Ok(None),
+        [DotToken::Id(id)] => Ok(Some((vec![id.clone()], attributes))),
+        _ =>
+        {
+            // a -> b -> c
+            let mut ids = vec![];
+            for (i, t) in head.iter().enumerate()
+            {
+                match (i % 2, t)
+                {
+                    (0, DotToken::Id(id)) => ids.push(id.clone()),
+                    (1, op) if *op == edge_op => {},
+                    (1, DotToken::Arrow) | (1, DotToken::Line) =>
+                        return Err("edge operator doesn't match the graph type".into()),
+                    _ => return Err("expected a node or edge statement".into())
+                }
+            }
+            if ids.len() < 2 || head.len() % 2 == 0
+            {
+                return Err("expected a node or edge statement".into());
+            }
+            Ok(Some((ids, attributes)))
+        }
+    }
+}
+
+fn dot_quote(id: &str) -> String
+{
+    format!("\"{}\"", id.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+enum XmlToken
+{
+    Open { line: usize, name: String, attributes: Vec<(String, String)>, closed: bool },
+    Close { name: String },
+    Text { text: String }
+}
+
+// Just enough of XML for GraphML: tags, attributes, text, comments and
+// processing instructions. Every token remembers the line it starts on.
+fn xml_tokens(input: &str) -> Result<Vec<XmlToken>, ParseError>
+{
+    let mut tokens = vec![];
+    let mut rest = input;
+    let mut line = 1;
+
+    while !rest.is_empty()
+    {
+        match rest.find('<')
+        {
+            Some(0) => {},
+            Some(i) =>
+            {
+                tokens.push(XmlToken::Text { text: xml_unescape(&rest[..i]) });
+                line += rest[..i].matches('\n').count();
+                rest = &rest[i..];
+            },
+            None =>
+            {
+                if !rest.trim().is_empty()
+                {
+                    return Err(ParseError::new(line, ParseErrorKind::Malformed(
+                        "text after the last element".into())));
+                }
+                break;
+            }
+        }
+        if rest.is_empty()
+        {
+            break;
+        }
+
+        let end_marker = if rest.starts_with("<!--") { "-->" }
+            else if rest.starts_with("<?") { "?>" }
+            else { ">" };
+        let end = match rest.find(end_marker)
+        {
+            Some(e) => e + end_marker.len(),
+            None => return Err(ParseError::new(line, ParseErrorKind::Malformed(
+                "unterminated tag".into())))
+        };
+        let tag = &rest[..end];
+        let start_line = line;
+        line += tag.matches('\n').count();
+        rest = &rest[end..];
+
+        if end_marker != ">" || tag.starts_with("<!")
+        {
+            continue;
+        }
+
+        let malformed = |m: &str| ParseError::new(start_line, ParseErrorKind::Malformed(m.into()));
+        if let Some(name) = tag.strip_prefix("</")
+        {
+            let name = name.trim_end_matches('>').trim().to_string();
+            tokens.push(XmlToken::Close { name });
+            continue;
+        }
+
+        let closed = tag.ends_with("/>");
+        let body = tag[1..tag.len() - if closed { 2 } else { 1 }].trim();
+        let name_end = body.find(char::is_whitespace).unwrap_or(body.len());
+        let name = body[..name_end].to_string();
+        if name.is_empty()
+        {
+            return Err(malformed("element without a name"));
+        }
+
+        let mut attributes = vec![];
+        let mut attrs = body[name_end..].trim_start();
+        while !attrs.is_empty()
+        {
+            let eq = attrs.find('=').ok_or_else(|| malformed("attribute without a value"))?;
+            let key = attrs[..eq].trim().to_string();
+            let value = attrs[eq + 1..].trim_start();
+            let quote = value.chars().next().filter(|c| *c == '"' || *c == '\'')
+                .ok_or_else(|| malformed("attribute value without quotes"))?;
+            let close = value[1..].find(quote).ok_or_else(|| malformed("unterminated attribute"))?;
+            attributes.push((key, xml_unescape(&value[1..close + 1])));
+            attrs = value[close + 2..].trim_start();
+        }
+        tokens.push(XmlToken::Open { line: start_line, name, attributes, closed });
+    }
+    Ok(tokens)
+}
+
+fn xml_escape(text: &str) -> String
+{
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn xml_unescape(text: &str) -> String
+{
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::InternetOfThings;
+
+    fn sample() -> InternetOfThings
+    {
+        let mut g = InternetOfThings::new();
+        g.set_nodes(vec![1, 2, 3, 4]);
+        g.set_edges(1, vec![(5, 2), (7, 3)]);
+        g.set_edges(2, vec![(1, 3)]);
+        g
+    }
+
+    fn same_graph(a: &InternetOfThings, b: &InternetOfThings)
+    {
+        assert_eq!(a.nodes(), b.nodes());
+        assert_eq!(a.edges(), b.edges());
+        for from in 1..5
+        {
+            for to in 1..5
+            {
+                assert_eq!(a.shorted_path(from, to), b.shorted_path(from, to));
+            }
+        }
+    }
+
+    #[test]
+    fn edge_list_csv_round_trip()
+    {
+        let g = sample();
+        let csv = g.to_edge_list_csv();
+        assert_eq!(csv, "# mode: directed multi\nfrom,to,weight\n1,2,5\n1,3,7\n2,3,1\n4\n");
+        same_graph(&g, &InternetOfThings::from_edge_list_csv(&csv).unwrap());
+    }
+
+    #[test]
+    fn edge_list_csv_quoting()
+    {
+        let mut g: Graph<String, u32> = Graph::new();
+        let keys = ["plain", "a,b", "say \"hi\"", "two\nlines", "#tag", " padded", ""];
+        g.set_nodes(keys.iter().map(|k| k.to_string()).collect());
+        for pair in keys.windows(2)
+        {
+            g.add_edge(pair[0].to_string(), pair[1].to_string(), 1).unwrap();
+        }
+        g.add_node("alone, too".to_string());
+
+        let csv = g.to_edge_list_csv();
+        assert!(csv.contains("plain,\"a,b\",1\n"));
+        assert!(csv.contains("\"a,b\",\"say \"\"hi\"\"\",1\n"));
+        assert!(csv.ends_with("\"alone, too\"\n"));
+        let back: Graph<String, u32> = Graph::from_edge_list_csv(&csv).unwrap();
+        assert_eq!(back.nodes(), g.nodes());
+        assert_eq!(back.edges(), g.edges());
+        assert_eq!(back.shorted_path("plain".to_string(), "".to_string()).map(|(w, _)| w), Some(6));
+
+        // Quoted fields keep their spaces, unquoted ones are trimmed
+        let g: Graph<String, u32> = Graph::from_edge_list_csv(" a , \" b \" , 3 ").unwrap();
+        let path = g.shorted_path("a".into(), " b ".into());
+        assert_eq!(path, Some((3, vec!["a".into(), " b ".into()])));
+
+        // A quoted header is data
+        let err = InternetOfThings::from_edge_list_csv("\"from\",to,weight").unwrap_err();
+        assert_eq!(err, ParseError::new(1, ParseErrorKind::InvalidWeight("weight".into())));
+
+        let err = InternetOfThings::from_edge_list_csv("1,2,3\n\"1\n,2,3").unwrap_err();
+        let kind = ParseErrorKind::Malformed("unterminated quoted field".into());
+        assert_eq!(err, ParseError::new(2, kind));
+        let err = InternetOfThings::from_edge_list_csv("1,\"2\"x,3").unwrap_err();
+        assert_eq!(err.line, 1);
+    }
+
+    #[test]
+    fn edge_list_csv_mode_and_header()
+    {
+        let mode = GraphMode::new(Direction::Undirected, Multiplicity::Simple);
+        let mut g = InternetOfThings::with_mode(mode);
+        g.set_nodes(vec![1, 2, 3]);
+        g.add_edge(1, 2, 4).unwrap();
+        g.add_edge(2, 3, 1).unwrap();
+
+        let csv = g.to_edge_list_csv();
+        assert!(csv.starts_with("# mode: undirected simple\n"));
+        let back = InternetOfThings::from_edge_list_csv(&csv).unwrap();
+        assert_eq!(back.mode(), mode);
+        assert_eq!(back.shorted_path(3, 1), Some((5, vec![3, 2, 1])));
+
+        // Loading checks the mode instead of changing it
+        let mut directed = InternetOfThings::new();
+        directed.set_nodes(vec![1, 2, 3]);
+        let err = directed.load_edge_list_csv(&csv).unwrap_err();
+        assert_eq!(err.line, 1);
+        assert!(matches!(err.kind, ParseErrorKind::Malformed(_)));
+
+        let err = InternetOfThings::from_edge_list_csv("# mode: sideways\n1,2,3").unwrap_err();
+        assert_eq!(err.line, 1);
+
+        // The header doesn't have to be on the first line
+        let g = InternetOfThings::from_edge_list_csv("\n\n from , to , weight\n1,2,3\n").unwrap();
+        assert_eq!(g.mode(), GraphMode::default());
+        assert_eq!(g.shorted_path(1, 2), Some((3, vec![1, 2])));
+        let csv = "# mode: directed simple\n\nfrom,to,weight\n1,2,3";
+        let g = InternetOfThings::from_edge_list_csv(csv).unwrap();
+        assert_eq!(g.edges(), 1);
+    }
+
+    #[test]
+    fn edge_list_csv_errors()
+    {
+        let err = InternetOfThings::from_edge_list_csv("1,2,5\n1,2\n").unwrap_err();
+        assert_eq!(err.line, 2);
+        assert!(matches!(err.kind, ParseErrorKind::Malformed(_)));
+
+        let err = InternetOfThings::from_edge_list_csv("1,2,five").unwrap_err();
+        assert_eq!(err, ParseError::new(1, ParseErrorKind::InvalidWeight("five".into())));
+
+        let err = InternetOfThings::from_edge_list_csv("from,to,weight\n1,x,5").unwrap_err();
+        assert_eq!(err, ParseError::new(2, ParseErrorKind::InvalidKey("x".into())));
+
+        let mut g = InternetOfThings::new();
+        g.set_nodes(vec![1, 2]);
+        let before = g.to_edge_list_csv();
+        let err = g.load_edge_list_csv("1,2,5\n\n2,9,1").unwrap_err();
+        assert_eq!(err, ParseError::new(3, ParseErrorKind::UnknownNode("9".into())));
+        // The valid first row must not have been applied either
+        assert_eq!(g.edges(), 0);
+        assert_eq!(g.to_edge_list_csv(), before);
+    }
+
+    #[test]
+    fn dot_round_trip()
+    {
+        let g = sample();
+        let dot = g.to_dot();
+        assert!(dot.contains("\"1\" -> \"2\" [weight=\"5\"];"));
+        same_graph(&g, &InternetOfThings::from_dot(&dot).unwrap());
+    }
+
+    #[test]
+    fn dot_parsing()
+    {
+        let dot = "// site A\ndigraph site {\n  rankdir=LR;\n  node [shape=box];\n  \
+                   1 -> 2 -> 3 [label=4];\n  3 -> 1 [weight=2, color=red]\n  9\n}\n";
+        let g = InternetOfThings::from_dot(dot).unwrap();
+        assert_eq!(g.nodes(), 4);
+        assert_eq!(g.shorted_path(1, 3), Some((8, vec![1, 2, 3])));
+        assert_eq!(g.shorted_path(3, 1), Some((2, vec![3, 1])));
+
+        let err = InternetOfThings::from_dot("digraph {\n  1 -> 2;\n}").unwrap_err();
+        assert_eq!(err.line, 2);
+        let err = InternetOfThings::from_dot("tree {\n}").unwrap_err();
+        assert_eq!(err.line, 1);
+        let err = InternetOfThings::from_dot("digraph {\n  1 -> 2 [weight=3]\n").unwrap_err();
+        assert_eq!(err.line, 2);
+        let err = InternetOfThings::from_dot("digraph {\n\n  1 -> x [weight=3]\n}").unwrap_err();
+        assert_eq!(err, ParseError::new(3, ParseErrorKind::InvalidKey("x".into())));
+    }
+
+    #[test]
+    fn dot_statements_on_one_line()
+    {
+        let dot = "digraph { rankdir=LR; 1 -> 2 [weight=3]; 2 -> 3 [weight=4] 3 -> 1 [label=1] \
+                   5 }";
+        let g = InternetOfThings::from_dot(dot).unwrap();
+        assert_eq!(g.nodes(), 4);
+        assert_eq!(g.edges(), 3);
+        assert_eq!(g.shorted_path(1, 3), Some((7, vec![1, 2, 3])));
+
+        // Attribute lists may span lines
+        let dot = "digraph {\n  1 -> 2 [\n    weight=2,\n    color=red\n  ]; 2 -> 1 [weight=6]\n}";
+        let g = InternetOfThings::from_dot(dot).unwrap();
+        assert_eq!(g.shorted_path(2, 1), Some((6, vec![2, 1])));
+
+        let dot = "digraph {\n 1 -> 2 [weight=1]; 2 -> 3 }";
+        let err = InternetOfThings::from_dot(dot).unwrap_err();
+        assert_eq!(err.line, 2);
+        let err = InternetOfThings::from_dot("digraph { 1 } 2").unwrap_err();
+        assert_eq!(err.line, 1);
+    }
+
+    #[test]
+    fn graphml_round_trip()
+    {
+        let g = sample();
+        same_graph(&g, &InternetOfThings::from_graphml(&g.to_graphml()).unwrap());
+    }
+
+    #[test]
+    fn graphml_errors()
+    {
+        let unknown = "<graphml>\n<key id=\"d0\" for=\"edge\" attr.name=\"weight\"/>\n<graph>\n\
+                       <node id=\"1\"/>\n<edge source=\"1\" target=\"2\">\n\
+                       <data key=\"d0\">3</data>\n</edge>\n</graph>\n</graphml>\n";
+        let err = InternetOfThings::from_graphml(unknown).unwrap_err();
+        assert_eq!(err, ParseError::new(5, ParseErrorKind::UnknownNode("2".into())));
+
+        let fixed = unknown.replace("<node id=\"1\"/>", "<node id=\"1\"/><node id=\"2\"/>");
+        let g = InternetOfThings::from_graphml(&fixed).unwrap();
+        assert_eq!(g.shorted_path(1, 2), Some((3, vec![1, 2])));
+
+        let no_weight = "<graphml><graph>\n<node id=\"1\"/>\n<edge source=\"1\" target=\"1\"/>\n\
+                         </graph></graphml>";
+        let err = InternetOfThings::from_graphml(no_weight).unwrap_err();
+        assert_eq!(err.line, 3);
+
+        // A node attribute called weight isn't the edge weight
+        let node_key = fixed.replace("for=\"edge\"", "for=\"node\"");
+        let err = InternetOfThings::from_graphml(&node_key).unwrap_err();
+        let kind = ParseErrorKind::Malformed("edge without a weight".into());
+        assert_eq!(err, ParseError::new(5, kind));
+        let all_key = fixed.replace("for=\"edge\"", "for=\"all\"");
+        assert!(InternetOfThings::from_graphml(&all_key).is_ok());
+    }
+
+    #[test]
+    fn undirected_round_trips()
+    {
+        let mode = GraphMode::new(Direction::Undirected, Multiplicity::Multi);
+        let mut g = InternetOfThings::with_mode(mode);
+        g.set_nodes(vec![1, 2, 3, 4]);
+        g.set_edges(1, vec![(5, 2), (7, 3)]);
+        g.set_edges(2, vec![(5, 1), (1, 3)]);
+
+        let dot = g.to_dot();
+        assert!(dot.starts_with("graph {"));
+        assert!(dot.contains("\"2\" -- \"3\""));
+        let from_dot = InternetOfThings::from_dot(&dot).unwrap();
+        assert_eq!(from_dot.mode(), mode);
+        same_graph(&g, &from_dot);
+
+        let from_graphml = InternetOfThings::from_graphml(&g.to_graphml()).unwrap();
+        assert_eq!(from_graphml.mode(), mode);
+        same_graph(&g, &from_graphml);
+
+        let from_csv = InternetOfThings::from_edge_list_csv(&g.to_edge_list_csv()).unwrap();
+        assert_eq!(from_csv.mode(), mode);
+        same_graph(&g, &from_csv);
+
+        let err = InternetOfThings::from_dot("graph {\n  1 -> 2 [weight=1]\n}").unwrap_err();
+        assert_eq!(err.line, 2);
+    }
+}
@@ -0,0 +1,194 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use super::{Edge, Graph, GraphMode};
+use super::traversal::{Adjacency, GraphView};
+
+// Read-only snapshot of a graph in compressed sparse row form: the edges of
+// node i are edges[offsets[i]..offsets[i + 1]] in one contiguous array.
+#[derive(Clone, Debug)]
+pub struct FrozenGraph<K, W>
+{
+    offsets: Vec<usize>,
+    edges: Vec<Edge<W>>,
+    nodes: Vec<K>,
+    index: HashMap<K, usize>,
+    mode: GraphMode
+}
+
+impl<K: Hash + Eq + Clone, W: Clone> Graph<K, W>
+{
+    pub fn freeze(self) -> FrozenGraph<K, W>
+    {
+        let mut offsets = Vec::with_capacity(self.nodes.len() + 1);
+        let mut edges = Vec::with_capacity(self.adjacency_list.iter().map(|l| l.len()).sum());
+        offsets.push(0);
+        for list in self.adjacency_list
+        {
+            edges.extend(list);
+            offsets.push(edges.len());
+        }
+        FrozenGraph { offsets, edges, nodes: self.nodes, index: self.index, mode: self.mode }
+    }
+}
+
+impl<K: Hash + Eq + Clone, W: Clone> FrozenGraph<K, W>
+{
+    pub fn thaw(self) -> Graph<K, W>
+    {
+        let mut edges = self.edges.into_iter();
+        let adjacency_list = self.offsets.windows(2)
+            .map(|w| edges.by_ref().take(w[1] - w[0]).collect())
+            .collect();
+        Graph
+        {
+            adjacency_list,
+            nodes: self.nodes,
+            index: self.index,
+            mode: self.mode
+        }
+    }
+
+    pub fn mode(&self) -> GraphMode
+    {
+        self.mode
+    }
+
+    pub fn contains_node(&self, node: K) -> bool
+    {
+        self.index.contains_key(&node)
+    }
+
+    pub fn edges(&self) -> u64
+    {
+        let stored = self.edges.len() as u64;
+        if self.mode.is_directed()
+        {
+            stored
+        } else {
+            // Self loops are the only edges stored once
+            let view = self.view();
+            let loops = (0..self.nodes.len()).fold(0u64, |p, i|
+            {
+                p + view.adjacent(i).iter().filter(|e| e.node == i).count() as u64
+            });
+            (stored + loops) / 2
+        }
+    }
+
+    pub fn nodes(&self) -> usize
+    {
+        self.nodes.len()
+    }
+
+    pub(crate) fn view(&self) -> GraphView<'_, K, W>
+    {
+        GraphView
+        {
+            nodes: &self.nodes,
+            index: &self.index,
+            edges: Adjacency::Compressed { offsets: &self.offsets, edges: &self.edges },
+            mode: self.mode
+        }
+    }
+}
+
+impl<K: Hash + Eq + Clone, W: Clone> From<Graph<K, W>> for FrozenGraph<K, W>
+{
+    fn from(graph: Graph<K, W>) -> FrozenGraph<K, W>
+    {
+        graph.freeze()
+    }
+}
+
+impl<K: Hash + Eq + Clone, W: Clone> From<FrozenGraph<K, W>> for Graph<K, W>
+{
+    fn from(graph: FrozenGraph<K, W>) -> Graph<K, W>
+    {
+        graph.thaw()
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use crate::test_support::{random_graph, Lcg};
+    use crate::{Direction, GraphMode, InternetOfThings, Multiplicity};
+
+    #[test]
+    fn frozen_matches_mutable()
+    {
+        let mut rng = Lcg(7);
+        for _ in 0..10
+        {
+            let g = random_graph(&mut rng, 40, 3);
+            let frozen = g.clone().freeze();
+            assert_eq!(frozen.nodes(), g.nodes());
+            assert_eq!(frozen.edges(), g.edges());
+            assert_eq!(frozen.all_pairs_shortest_paths(), g.all_pairs_shortest_paths());
+            for n in 0..40
+            {
+                let key = n * 10;
+                assert_eq!(frozen.connected(key, 2), g.connected(key, 2));
+                assert_eq!(
+                    frozen.connected_with_distance(key, 3),
+                    g.connected_with_distance(key, 3)
+                );
+                let target = rng.key(40);
+                assert_eq!(
+                    frozen.shorted_path(key, target).map(|(cost, _)| cost),
+                    g.shorted_path(key, target).map(|(cost, _)| cost)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn frozen_shares_the_algorithms()
+    {
+        let mut rng = Lcg(11);
+        for _ in 0..5
+        {
+            let g = random_graph(&mut rng, 20, 3);
+            let frozen = g.clone().freeze();
+            assert_eq!(frozen.weakly_connected_components(), g.weakly_connected_components());
+            assert_eq!(frozen.strongly_connected_components(), g.strongly_connected_components());
+            assert_eq!(frozen.topological_order(), g.topological_order());
+            assert_eq!(frozen.betweenness_centrality(), g.betweenness_centrality());
+            assert_eq!(frozen.closeness_centrality(), g.closeness_centrality());
+            assert_eq!(frozen.eccentricity(), g.eccentricity());
+            assert_eq!(frozen.page_rank(0.85, 20), g.page_rank(0.85, 20));
+            assert_eq!(frozen.greedy_colouring(), g.greedy_colouring());
+            assert_eq!(frozen.max_flow(0, 10), g.max_flow(0, 10));
+            for (from, to) in rng.pairs(20, 20)
+            {
+                assert_eq!(frozen.a_star(from, to, |_, _| 0), g.a_star(from, to, |_, _| 0));
+                assert_eq!(frozen.k_shortest_paths(from, to, 3), g.k_shortest_paths(from, to, 3));
+                assert_eq!(frozen.bellman_ford(from, to), g.bellman_ford(from, to));
+            }
+        }
+    }
+
+    #[test]
+    fn thaw_round_trip()
+    {
+        let mode = GraphMode::new(Direction::Undirected, Multiplicity::Simple);
+        let mut g = InternetOfThings::with_mode(mode);
+        g.set_nodes(vec![1, 2, 3, 4]);
+        g.add_edge(1, 2, 5).unwrap();
+        g.add_edge(2, 3, 1).unwrap();
+        g.add_edge(3, 3, 1).unwrap();
+
+        let frozen = g.clone().freeze();
+        assert_eq!(frozen.mode(), mode);
+        assert_eq!(frozen.edges(), 3);
+        assert!(frozen.contains_node(4));
+        assert_eq!(frozen.shorted_path(3, 1), Some((6, vec![3, 2, 1])));
+        assert_eq!(frozen.shorted_path(1, 4), None);
+
+        let mut thawed = frozen.thaw();
+        assert_eq!(thawed.to_edge_list_csv(), g.to_edge_list_csv());
+        thawed.add_edge(4, 1, 1).unwrap();
+        assert_eq!(thawed.shorted_path(3, 4), Some((7, vec![3, 2, 1, 4])));
+    }
+}
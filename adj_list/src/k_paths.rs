@@ -0,0 +1,411 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashSet};
+use std::hash::Hash;
+
+use super::traversal::{forward_to_view, GraphView};
+use super::{Edge, KeyType, NonNegative, TentativeWeight};
+
+// Restrictions for route searches. Edges are given as (from, to) and cover
+// every parallel edge between the two nodes; in undirected graphs both
+// directions are avoided.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PathConstraints<K = KeyType>
+{
+    pub avoid_nodes: Vec<K>,
+    pub avoid_edges: Vec<(K, K)>,
+    pub max_hops: Option<usize>
+}
+
+impl<K> Default for PathConstraints<K>
+{
+    fn default() -> Self
+    {
+        PathConstraints
+        {
+            avoid_nodes: vec![],
+            avoid_edges: vec![],
+            max_hops: None
+        }
+    }
+}
+
+// A path by node index, with the cost of reaching each of its nodes
+struct IndexPath<W>
+{
+    nodes: Vec<usize>,
+    costs: Vec<W>
+}
+
+forward_to_view!
+{
+    [K: Hash + Eq + Clone, W: NonNegative] (K, W)
+    fn constrained_shortest_path(&self, from: K, to: K, constraints: &PathConstraints<K>)
+        -> Option<(W, Vec<K>)>;
+    fn k_shortest_paths(&self, from: K, to: K, k: usize) -> Vec<(W, Vec<K>)>;
+    fn constrained_k_shortest_paths(&self, from: K, to: K, k: usize,
+        constraints: &PathConstraints<K>) -> Vec<(W, Vec<K>)>;
+}
+
+impl<'a, K: Hash + Eq + Clone, W: NonNegative> GraphView<'a, K, W>
+{
+    pub(crate) fn constrained_shortest_path(&self, from: K, to: K, constraints: &PathConstraints<K>)
+        -> Option<(W, Vec<K>)>
+    {
+        self.constrained_k_shortest_paths(from, to, 1, constraints).pop()
+    }
+
+    // Yen's algorithm: up to k loopless paths, cheapest first
+    pub(crate) fn k_shortest_paths(&self, from: K, to: K, k: usize) -> Vec<(W, Vec<K>)>
+    {
+        self.constrained_k_shortest_paths(from, to, k, &PathConstraints::default())
+    }
+
+    pub(crate) fn constrained_k_shortest_paths(
+        &self,
+        from: K,
+        to: K,
+        k: usize,
+        constraints: &PathConstraints<K>
+    ) -> Vec<(W, Vec<K>)>
+    {
+        let (src, dst) = match (self.get_node_index(&from), self.get_node_index(&to))
+        {
+            (Some(src), Some(dst)) => (src, dst),
+            _ => return vec![]
+        };
+
+        let mut banned_nodes = vec![false; self.nodes.len()];
+        for n in constraints.avoid_nodes.iter().filter_map(|n| self.get_node_index(n))
+        {
+            banned_nodes[n] = true;
+        }
+        let mut banned_edges = HashSet::new();
+        for (a, b) in constraints.avoid_edges.iter()
+        {
+            if let (Some(a), Some(b)) = (self.get_node_index(a), self.get_node_index(b))
+            {
+                banned_edges.insert((a, b));
+                if !self.mode.is_directed()
+                {
+                    banned_edges.insert((b, a));
+                }
+            }
+        }
+
+        if k == 0
+        {
+            return vec![];
+        }
+        let mut found: Vec<IndexPath<W>> = vec![];
+        match self.restricted_path(src, dst, &banned_nodes, &banned_edges, constraints.max_hops)
+        {
+            Some(path) => found.push(path),
+            None => return vec![]
+        }
+
+        let mut candidates: Vec<IndexPath<W>> = vec![];
+        let mut seen: HashSet<Vec<usize>> = vec![found[0].nodes.clone()].into_iter().collect();
+        while found.len() < k
+        {
+            let previous = &found[found.len() - 1];
+            for i in 0..previous.nodes.len() - 1
+            {
+                let spur = previous.nodes[i];
+                let root = &previous.nodes[..=i];
+
+                // Leave the root the same way as none of the paths found so far
+                let mut spur_edges = banned_edges.clone();
+                for p in found.iter().filter(|p| p.nodes.len() > i + 1 && &p.nodes[..=i] == root)
+                {
+                    spur_edges.insert((spur, p.nodes[i + 1]));
+                }
+                let mut spur_nodes = banned_nodes.clone();
+                for n in &root[..i]
+                {
+                    spur_nodes[*n] = true;
+                }
+
+                let hops = match constraints.max_hops
+                {
+                    Some(max) if max <= i => continue,
+                    Some(max) => Some(max - i),
+                    None => None
+                };
+                let spur_path = self.restricted_path(spur, dst, &spur_nodes, &spur_edges, hops);
+                let spur_path = match spur_path
+                {
+                    Some(p) => p,
+                    None => continue
+                };
+
+                let mut nodes = root.to_vec();
+                nodes.extend_from_slice(&spur_path.nodes[1..]);
+                if seen.contains(&nodes)
+                {
+                    continue;
+                }
+                let mut costs = previous.costs[..=i].to_vec();
+                let base = previous.costs[i];
+                let mut overflow = false;
+                for c in &spur_path.costs[1..]
+                {
+                    match base.checked_add(*c)
+                    {
+                        Some(total) => costs.push(total),
+                        None => overflow = true
+                    }
+                }
+                if !overflow
+                {
+                    seen.insert(nodes.clone());
+                    candidates.push(IndexPath { nodes, costs });
+                }
+            }
+
+            // Cheapest candidate next, fewer hops breaking ties
+            let best = candidates.iter().enumerate().min_by(|(_, a), (_, b)|
+            {
+                (a.costs.last(), a.nodes.len()).cmp(&(b.costs.last(), b.nodes.len()))
+            }).map(|(i, _)| i);
+            match best
+            {
+                Some(i) => found.push(candidates.swap_remove(i)),
+                None => break
+            }
+        }
+
+        found.into_iter().map(|p|
+        {
+            let cost = *p.costs.last().unwrap();
+            (cost, p.nodes.into_iter().map(|n| self.nodes[n].clone()).collect())
+        }).collect()
+    }
+
+    // Cheapest path that steers clear of the banned nodes and edges, using at
+    // most `max_hops` edges when given
+    fn restricted_path(
+        &self,
+        src: usize,
+        dst: usize,
+        banned_nodes: &[bool],
+        banned_edges: &HashSet<(usize, usize)>,
+        max_hops: Option<usize>
+    ) -> Option<IndexPath<W>>
+    {
+        if banned_nodes[src] || banned_nodes[dst]
+        {
+            return None;
+        }
+        let usable = |u: usize, e: &Edge<W>|
+        {
+            !banned_nodes[e.node] && !banned_edges.contains(&(u, e.node))
+        };
+
+        match max_hops
+        {
+            None =>
+            {
+                let mut distance = vec![TentativeWeight::Infinite; self.nodes.len()];
+                distance[src] = TentativeWeight::Number(W::zero());
+                let mut parent = vec![None; self.nodes.len()];
+                let mut open = BinaryHeap::new();
+                open.push(Reverse((TentativeWeight::Number(W::zero()), src)));
+
+                while let Some(Reverse((dist, u))) = open.pop()
+                {
+                    if dist > distance[u]
+                    {
+                        continue;
+                    }
+                    if u == dst
+                    {
+                        break;
+                    }
+                    for e in self.adjacent(u).iter().filter(|e| usable(u, e))
+                    {
+                        let new_distance = dist.add(e.weight);
+                        if new_distance < distance[e.node]
+                        {
+                            distance[e.node] = new_distance.clone();
+                            parent[e.node] = Some(u);
+                            open.push(Reverse((new_distance, e.node)));
+                        }
+                    }
+                }
+
+                if let TentativeWeight::Number(_) = distance[dst]
+                {
+                    let mut nodes = vec![dst];
+                    while let Some(p) = parent[*nodes.last().unwrap()]
+                    {
+                        nodes.push(p);
+                    }
+                    nodes.reverse();
+                    let costs = nodes.iter().map(|n| match distance[*n]
+                    {
+                        TentativeWeight::Number(c) => c,
+                        _ => unreachable!()
+                    }).collect();
+                    Some(IndexPath { nodes, costs })
+                } else {
+                    None
+                }
+            },
+            Some(max_hops) =>
+            {
+                // Bellman-Ford style rounds: after round h, layers[h] holds the
+                // cheapest cost with at most h edges
+                let mut layers = vec![vec![TentativeWeight::Infinite; self.nodes.len()]];
+                let mut parents: Vec<Vec<Option<usize>>> = vec![vec![None; self.nodes.len()]];
+                layers[0][src] = TentativeWeight::Number(W::zero());
+
+                for h in 0..max_hops
+                {
+                    let mut next = layers[h].clone();
+                    let mut parent = vec![None; self.nodes.len()];
+                    for u in 0..self.nodes.len()
+                    {
+                        if banned_nodes[u] || layers[h][u] == TentativeWeight::Infinite
+                        {
+                            continue;
+                        }
+                        for e in self.adjacent(u).iter().filter(|e| usable(u, e) && e.node != src)
+                        {
+                            let new_distance = layers[h][u].add(e.weight);
+                            if new_distance < next[e.node]
+                            {
+                                next[e.node] = new_distance;
+                                parent[e.node] = Some(u);
+                            }
+                        }
+                    }
+                    layers.push(next);
+                    parents.push(parent);
+                }
+
+                if let TentativeWeight::Number(_) = layers[max_hops][dst]
+                {
+                    // Walk back through the layers; a node without a parent in
+                    // a layer kept the cost it had one layer earlier
+                    let mut nodes = vec![dst];
+                    let mut costs = vec![];
+                    let mut h = max_hops;
+                    let mut node = dst;
+                    while node != src
+                    {
+                        while parents[h][node].is_none()
+                        {
+                            h -= 1;
+                        }
+                        if let TentativeWeight::Number(c) = layers[h][node].clone()
+                        {
+                            costs.push(c);
+                        }
+                        node = parents[h][node].unwrap();
+                        h -= 1;
+                        nodes.push(node);
+                    }
+                    costs.push(W::zero());
+                    nodes.reverse();
+                    costs.reverse();
+                    Some(IndexPath { nodes, costs })
+                } else {
+                    None
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::test_support::{random_graph, Lcg};
+    use crate::InternetOfThings;
+
+    // Two routes around a square plus a long detour
+    fn network() -> InternetOfThings
+    {
+        let mut g = InternetOfThings::new();
+        g.set_nodes(vec![1, 2, 3, 4, 5, 6]);
+        g.set_edges(1, vec![(1, 2), (2, 3), (10, 6)]);
+        g.set_edges(2, vec![(1, 4), (5, 3)]);
+        g.set_edges(3, vec![(1, 4)]);
+        g.set_edges(4, vec![(1, 6)]);
+        g.set_edges(5, vec![(1, 6)]);
+        g
+    }
+
+    #[test]
+    fn yen_k_shortest()
+    {
+        let g = network();
+        let paths = g.k_shortest_paths(1, 6, 10);
+        assert_eq!(
+            paths,
+            vec![
+                (3, vec![1, 2, 4, 6]),
+                (4, vec![1, 3, 4, 6]),
+                (8, vec![1, 2, 3, 4, 6]),
+                (10, vec![1, 6])
+            ]
+        );
+        assert_eq!(g.k_shortest_paths(1, 6, 2).len(), 2);
+        assert!(g.k_shortest_paths(1, 6, 0).is_empty());
+        assert!(g.k_shortest_paths(6, 1, 3).is_empty());
+        assert_eq!(g.k_shortest_paths(1, 5, 3), vec![]);
+    }
+
+    #[test]
+    fn avoiding_nodes_and_edges()
+    {
+        let g = network();
+        let avoid_two = PathConstraints { avoid_nodes: vec![2], ..Default::default() };
+        assert_eq!(g.constrained_shortest_path(1, 6, &avoid_two), Some((4, vec![1, 3, 4, 6])));
+
+        let avoid_links =
+            PathConstraints { avoid_edges: vec![(2, 4), (3, 4)], ..Default::default() };
+        assert_eq!(g.constrained_shortest_path(1, 6, &avoid_links), Some((10, vec![1, 6])));
+
+        let avoid_target = PathConstraints { avoid_nodes: vec![6], ..Default::default() };
+        assert_eq!(g.constrained_shortest_path(1, 6, &avoid_target), None);
+    }
+
+    #[test]
+    fn hop_limit()
+    {
+        let g = network();
+        let one_hop = PathConstraints { max_hops: Some(1), ..Default::default() };
+        assert_eq!(g.constrained_shortest_path(1, 6, &one_hop), Some((10, vec![1, 6])));
+
+        let three_hops = PathConstraints { max_hops: Some(3), ..Default::default() };
+        let paths = g.constrained_k_shortest_paths(1, 6, 10, &three_hops);
+        assert_eq!(paths, vec![(3, vec![1, 2, 4, 6]), (4, vec![1, 3, 4, 6]), (10, vec![1, 6])]);
+
+        let zero_hops = PathConstraints { max_hops: Some(0), ..Default::default() };
+        assert_eq!(g.constrained_shortest_path(1, 1, &zero_hops), Some((0, vec![1])));
+        assert_eq!(g.constrained_shortest_path(1, 6, &zero_hops), None);
+    }
+
+    #[test]
+    fn k_shortest_on_random_graphs()
+    {
+        let mut rng = Lcg(5);
+        for _ in 0..5
+        {
+            let g = random_graph(&mut rng, 30, 4);
+            for (from, to) in rng.pairs(30, 10)
+            {
+                let paths = g.k_shortest_paths(from, to, 5);
+                assert_eq!(paths.first().map(|p| p.0), g.shorted_path(from, to).map(|p| p.0));
+                assert!(paths.windows(2).all(|w| w[0].0 <= w[1].0));
+                for (_, path) in paths.iter()
+                {
+                    let unique: HashSet<&KeyType> = path.iter().collect();
+                    assert_eq!(unique.len(), path.len());
+                }
+            }
+        }
+    }
+}
@@ -1,78 +1,294 @@
 // This is what we'll be using most of the time. Memorize everything you can.
 
 use std::cmp::{Ord, Ordering};
-use std::collections::HashSet;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+use std::hash::Hash;
+use std::mem;
+
+mod astar;
+mod colouring;
+mod components;
+mod events;
+mod flow;
+mod formats;
+mod frozen;
+mod k_paths;
+mod metrics;
+mod mode;
+#[cfg(feature = "parallel")]
+mod parallel;
+mod signed;
+mod snapshot;
+mod spanning_tree;
+#[cfg(test)]
+mod test_support;
+mod traversal;
+mod union_find;
+mod weight;
+
+pub use colouring::OddCycle;
+pub use components::CycleError;
+pub use events::{GraphEvent, ObservedGraph, ObserverId};
+pub use flow::MaxFlow;
+pub use formats::{ParseError, ParseErrorKind};
+pub use frozen::FrozenGraph;
+pub use k_paths::PathConstraints;
+pub use mode::{Direction, GraphMode, Multiplicity};
+pub use signed::SignedInternetOfThings;
+pub use snapshot::{SnapshotError, SnapshotValue};
+pub use traversal::BreadthFirst;
+use traversal::{forward_to_view, Adjacency, GraphView};
+pub use union_find::UnionFind;
+pub use weight::{NegativeWeight, NonNegative, NonNegativeF64, Numeric, TotalF64, Weight};
 
 type KeyType = u64;
+// Cost and path to every node reached from one source
+type PathTree<K, W> = HashMap<K, (W, Vec<K>)>;
+
+pub type InternetOfThings = Graph<KeyType, u32>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PathError<K = KeyType>
+{
+    // The cheapest path to the node costs more than the weight type can hold
+    Overflow(K),
+    // The cheapest path to the node costs less than the weight type can hold,
+    // which takes negative weights
+    Underflow(K),
+    // Nodes of a negative cycle reachable from the source, first node repeated at the end
+    NegativeCycle(Vec<K>)
+}
+
+impl<K: fmt::Debug> fmt::Display for PathError<K>
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        match self
+        {
+            PathError::Overflow(node) =>
+                write!(f, "Path cost to node {:?} overflows the weight type", node),
+            PathError::Underflow(node) =>
+                write!(f, "Path cost to node {:?} underflows the weight type", node),
+            PathError::NegativeCycle(cycle) =>
+                write!(f, "Negative cycle reachable from the source: {:?}", cycle)
+        }
+    }
+}
+
+impl<K: fmt::Debug> Error for PathError<K> {}
+
+// Nodes an edge operation referred to that aren't in the graph
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnknownNodes<K = KeyType>(pub Vec<K>);
+
+impl<K: fmt::Debug> fmt::Display for UnknownNodes<K>
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        write!(f, "Unknown nodes: {:?}", self.0)
+    }
+}
+
+impl<K: fmt::Debug> Error for UnknownNodes<K> {}
 
-pub struct InternetOfThings
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(
+    try_from = "snapshot::GraphData<K, W>",
+    bound(deserialize = "K: serde::Deserialize<'de> + Hash + Eq + Clone, \
+                         W: serde::Deserialize<'de> + Clone + PartialEq")
+))]
+pub struct Graph<K, W>
 {
-    adjacency_list: Vec<Vec<Edge>>,
-    nodes: Vec<KeyType>
+    adjacency_list: Vec<Vec<Edge<W>>>,
+    nodes: Vec<K>,
+    // Rebuilt from the nodes when deserialising
+    #[cfg_attr(feature = "serde", serde(skip))]
+    index: HashMap<K, usize>,
+    mode: GraphMode
 }
 
 #[derive(Eq, PartialEq, Clone, Debug)]
-enum TentativeWeight
+enum TentativeWeight<W>
 {
     Infinite,
-    Number(u32)
+    // Reachable, but the cost no longer fits into the edge weight type
+    Overflow,
+    // Like `Overflow`, for costs below the smallest weight
+    Underflow,
+    Number(W)
 }
 
-impl Ord for TentativeWeight
+impl<W: Weight> TentativeWeight<W>
 {
-    fn cmp(&self, other: &TentativeWeight) -> Ordering
+    fn add(&self, weight: W) -> TentativeWeight<W>
     {
-        match other
+        match self
         {
-            TentativeWeight::Infinite => match self
+            TentativeWeight::Number(n) => match n.checked_add(weight)
             {
-                TentativeWeight::Infinite => Ordering::Equal,
-                _ => Ordering::Less,
+                Some(sum) => TentativeWeight::Number(sum),
+                None if weight < W::zero() => TentativeWeight::Underflow,
+                None => TentativeWeight::Overflow
             },
-            TentativeWeight::Number(n) => match self
-            {
-                TentativeWeight::Infinite => Ordering::Greater,
-                TentativeWeight::Number(s) => s.cmp(n)
-            }
+            other => other.clone()
+        }
+    }
+}
+
+impl<W> TentativeWeight<W>
+{
+    // Underflow < Number < Overflow < Infinite
+    fn rank(&self) -> u8
+    {
+        match self
+        {
+            TentativeWeight::Underflow => 0,
+            TentativeWeight::Number(_) => 1,
+            TentativeWeight::Overflow => 2,
+            TentativeWeight::Infinite => 3
+        }
+    }
+}
+
+impl<W: Ord> Ord for TentativeWeight<W>
+{
+    fn cmp(&self, other: &TentativeWeight<W>) -> Ordering
+    {
+        match (self, other)
+        {
+            (TentativeWeight::Number(s), TentativeWeight::Number(n)) => s.cmp(n),
+            _ => self.rank().cmp(&other.rank())
         }
     }
 }
 
-impl PartialOrd for TentativeWeight
+impl<W: Ord> PartialOrd for TentativeWeight<W>
 {
-    fn partial_cmp(&self, other: &TentativeWeight) -> Option<Ordering>
+    fn partial_cmp(&self, other: &TentativeWeight<W>) -> Option<Ordering>
     {
         Some(self.cmp(other))
     }
 }
 
 #[derive(Clone, Debug)]
-struct Edge
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct Edge<W>
 {
-    weight: u32,
+    weight: W,
     node: usize,
 }
 
-impl InternetOfThings
+impl<K: Hash + Eq + Clone, W: Clone> Default for Graph<K, W>
 {
-    pub fn new() -> InternetOfThings
+    fn default() -> Self
     {
-        InternetOfThings
+        Graph::new()
+    }
+}
+
+impl<K: Hash + Eq + Clone, W: Clone> Graph<K, W>
+{
+    pub fn new() -> Graph<K, W>
+    {
+        Graph::with_mode(GraphMode::default())
+    }
+
+    pub fn with_mode(mode: GraphMode) -> Graph<K, W>
+    {
+        Graph
         {
             adjacency_list: vec![],
-            nodes: vec![]
+            nodes: vec![],
+            index: HashMap::new(),
+            mode
         }
     }
 
-    pub fn set_nodes(&mut self, nodes: Vec<KeyType>)
+    pub fn mode(&self) -> GraphMode
     {
+        self.mode
+    }
+
+    pub fn set_nodes(&mut self, nodes: Vec<K>)
+    {
+        self.index = HashMap::with_capacity(nodes.len());
+        for (i, n) in nodes.iter().enumerate()
+        {
+            self.index.entry(n.clone()).or_insert(i);
+        }
         self.nodes = nodes;
         self.adjacency_list = vec![vec![]; self.nodes.len()]
     }
 
+    pub fn contains_node(&self, node: K) -> bool
+    {
+        self.index.contains_key(&node)
+    }
+
+    pub fn add_node(&mut self, node: K) -> bool
+    {
+        if self.index.contains_key(&node)
+        {
+            false
+        } else {
+            self.index.insert(node.clone(), self.nodes.len());
+            self.nodes.push(node);
+            self.adjacency_list.push(vec![]);
+            true
+        }
+    }
+
+    pub fn remove_node(&mut self, node: K) -> bool
+    {
+        let removed = match self.index.remove(&node)
+        {
+            Some(i) => i,
+            None => return false
+        };
+
+        self.nodes.remove(removed);
+        self.adjacency_list.remove(removed);
+
+        // Every node after the removed one moves down by one slot
+        for i in self.index.values_mut()
+        {
+            if *i > removed
+            {
+                *i -= 1;
+            }
+        }
+        for edges in self.adjacency_list.iter_mut()
+        {
+            edges.retain(|e| e.node != removed);
+            for e in edges.iter_mut()
+            {
+                if e.node > removed
+                {
+                    e.node -= 1;
+                }
+            }
+        }
+        true
+    }
+
     pub fn edges(&self) -> u64
     {
-        self.adjacency_list.iter().fold(0u64, |p, c| p + c.len() as u64)
+        let stored = self.adjacency_list.iter().fold(0u64, |p, c| p + c.len() as u64);
+        if self.mode.is_directed()
+        {
+            stored
+        } else {
+            // Self loops are the only edges stored once
+            let loops = self.adjacency_list.iter().enumerate().fold(0u64, |p, (i, c)|
+            {
+                p + c.iter().filter(|e| e.node == i).count() as u64
+            });
+            (stored + loops) / 2
+        }
     }
 
     pub fn nodes(&self) -> usize
@@ -80,159 +296,736 @@ impl InternetOfThings
         self.nodes.len()
     }
 
-    pub fn set_edges(&mut self, from: KeyType, edges: Vec<(u32, KeyType)>)
+    pub fn set_edges(&mut self, from: K, edges: Vec<(W, K)>)
     {
-        let edges: Vec<Edge> = edges.into_iter().filter_map(|e|
+        let edges: Vec<(W, usize)> = edges.into_iter().filter_map(|e|
         {
-            if let Some(to) = self.get_node_index(e.1)
-            {
-                Some(Edge { weight: e.0, node: to })
-            } else {
-                None
-            }}).collect();
-        match self.nodes.iter().position(|n| n == &from)
+            self.get_node_index(&e.1).map(|to| (e.0, to))
+        }).collect();
+        let from = match self.get_node_index(&from)
         {
-            Some(i) => self.adjacency_list[i] = edges,
+            Some(i) => i,
             None =>
             {
-                self.nodes.push(from);
-                self.adjacency_list.push(edges)
+                self.add_node(from);
+                self.nodes.len() - 1
+            }
+        };
+
+        let old = mem::take(&mut self.adjacency_list[from]);
+        if !self.mode.is_directed()
+        {
+            for e in old.iter().filter(|e| e.node != from)
+            {
+                self.adjacency_list[e.node].retain(|mirror| mirror.node != from);
+            }
+        }
+        for (weight, to) in edges
+        {
+            self.insert_edge(from, to, weight);
+        }
+    }
+
+    // Like `set_edges`, but leaves the graph untouched and lists the missing
+    // targets instead of dropping their edges
+    pub fn set_edges_strict(&mut self, from: K, edges: Vec<(W, K)>)
+        -> Result<(), UnknownNodes<K>>
+    {
+        let mut unknown: Vec<K> = vec![];
+        for (_, to) in edges.iter()
+        {
+            if !self.index.contains_key(to) && !unknown.contains(to)
+            {
+                unknown.push(to.clone());
+            }
+        }
+        if unknown.is_empty()
+        {
+            self.set_edges(from, edges);
+            Ok(())
+        } else {
+            Err(UnknownNodes(unknown))
+        }
+    }
+
+    // Like `set_edges`, but creates any target node that doesn't exist yet
+    pub fn set_edges_lenient(&mut self, from: K, edges: Vec<(W, K)>)
+    {
+        for (_, to) in edges.iter()
+        {
+            if !self.index.contains_key(to)
+            {
+                self.add_node(to.clone());
             }
         }
+        self.set_edges(from, edges);
     }
 
-    pub fn connected(&self, from: KeyType, degree: usize) -> Option<HashSet<KeyType>>
+    pub fn add_edge(&mut self, from: K, to: K, weight: W) -> Result<(), UnknownNodes<K>>
     {
-        self.nodes.iter().position(|n| n == &from).map(|i|
+        match (self.get_node_index(&from), self.get_node_index(&to))
         {
-            self.connected_r(i, degree).into_iter().map(|n|{
-                self.nodes[n].clone()}).collect()
-        })
+            (Some(from), Some(to)) =>
+            {
+                self.insert_edge(from, to, weight);
+                Ok(())
+            },
+            (from_index, to_index) =>
+            {
+                let mut unknown = vec![];
+                if from_index.is_none()
+                {
+                    unknown.push(from.clone());
+                }
+                if to_index.is_none() && (from_index.is_some() || from != to)
+                {
+                    unknown.push(to);
+                }
+                Err(UnknownNodes(unknown))
+            }
+        }
     }
 
-    pub fn shorted_path(&self, from: KeyType, to: KeyType) -> Option<(u32, Vec<KeyType>)>
+    // Returns the weight of the removed edge
+    pub fn remove_edge(&mut self, from: K, to: K) -> Option<W>
     {
-        let mut src = None;
-        let mut dst = None;
+        let (from, to) = (self.get_node_index(&from)?, self.get_node_index(&to)?);
+        let position = self.adjacency_list[from].iter().position(|e| e.node == to)?;
+        let removed = self.adjacency_list[from].remove(position).weight;
 
-        for (i, n) in self.nodes.iter().enumerate()
+        // Parallel edges sit in the same order in both lists, so the first
+        // mirror is the one that belongs to the removed edge
+        if !self.mode.is_directed() && from != to
         {
-            if n == &from{
-                src = Some(i);
+            if let Some(p) = self.adjacency_list[to].iter().position(|e| e.node == from)
+            {
+                self.adjacency_list[to].remove(p);
             }
-            if n == &to
+        }
+        Some(removed)
+    }
+
+    // Returns the previous weight, or None when there is no such edge
+    pub fn update_weight(&mut self, from: K, to: K, weight: W) -> Option<W>
+    {
+        let (from, to) = (self.get_node_index(&from)?, self.get_node_index(&to)?);
+        if !self.mode.is_directed() && from != to
+        {
+            if let Some(mirror) = self.adjacency_list[to].iter_mut().find(|e| e.node == from)
             {
-                dst = Some(i);
+                mirror.weight = weight.clone();
             }
-            if src.is_some() && dst.is_some()
+        }
+        let edge = self.adjacency_list[from].iter_mut().find(|e| e.node == to)?;
+        Some(mem::replace(&mut edge.weight, weight))
+    }
+
+    // Every edge once, also for undirected graphs where both directions are stored
+    fn edge_list(&self) -> impl Iterator<Item = (usize, &Edge<W>)>
+    {
+        self.view().edge_list()
+    }
+
+    pub(crate) fn view(&self) -> GraphView<'_, K, W>
+    {
+        GraphView
+        {
+            nodes: &self.nodes,
+            index: &self.index,
+            edges: Adjacency::Lists(&self.adjacency_list),
+            mode: self.mode
+        }
+    }
+
+    // Adds an edge according to the graph's mode: mirrored when undirected,
+    // replacing an existing one between the same nodes when simple
+    fn insert_edge(&mut self, from: usize, to: usize, weight: W)
+    {
+        let directions = if self.mode.is_directed() || from == to
+        {
+            vec![(from, to)]
+        } else {
+            vec![(from, to), (to, from)]
+        };
+
+        for (a, b) in directions
+        {
+            let existing = match self.mode.multiplicity
             {
-                break;
+                Multiplicity::Simple => self.adjacency_list[a].iter_mut().find(|e| e.node == b),
+                Multiplicity::Multi => None
+            };
+            match existing
+            {
+                Some(e) => e.weight = weight.clone(),
+                None => self.adjacency_list[a].push(Edge { weight: weight.clone(), node: b })
+            }
+        }
+    }
+
+    fn get_node_index(&self, node: &K) -> Option<usize>
+    {
+        self.index.get(node).copied()
+    }
+}
+
+forward_to_view!
+{
+    [K: Hash + Eq + Clone, W: Clone] (K, W)
+    fn connected(&self, from: K, degree: usize) -> Option<HashSet<K>>;
+}
+
+forward_to_view!
+{
+    [K: Hash + Eq + Clone, W: NonNegative] (K, W)
+    fn shorted_path(&self, from: K, to: K) -> Option<(W, Vec<K>)>;
+    fn try_shorted_path(&self, from: K, to: K) -> Result<Option<(W, Vec<K>)>, PathError<K>>;
+    fn shortest_paths_from(&self, from: K) -> HashMap<K, (W, Vec<K>)>;
+    fn try_shortest_paths_from(&self, from: K) -> Result<PathTree<K, W>, PathError<K>>;
+    fn all_pairs_shortest_paths(&self) -> HashMap<K, HashMap<K, W>>;
+    fn try_all_pairs_shortest_paths(&self) -> Result<HashMap<K, HashMap<K, W>>, PathError<K>>;
+}
+
+impl<'a, K: Hash + Eq + Clone, W: Clone> GraphView<'a, K, W>
+{
+    pub(crate) fn connected(&self, from: K, degree: usize) -> Option<HashSet<K>>
+    {
+        let src = self.get_node_index(&from)?;
+        let mut connected = HashSet::new();
+        let within_reach = self.breadth_first(from.clone())?
+            .take_while(|(_, hops)| *hops <= degree);
+        for (node, hops) in within_reach
+        {
+            // The start node only counts when a cycle leads back to it in time
+            let back_to_start = self.adjacent(self.index[&node]).iter().any(|e| e.node == src);
+            if hops < degree && back_to_start
+            {
+                connected.insert(from.clone());
+            }
+            if hops > 0
+            {
+                connected.insert(node);
             }
         }
+        Some(connected)
+    }
+}
+
+impl<'a, K: Hash + Eq + Clone, W: NonNegative> GraphView<'a, K, W>
+{
+    // None also when the cheapest path overflows the weight type, which
+    // `try_shorted_path` tells apart from there being no path at all
+    pub(crate) fn shorted_path(&self, from: K, to: K) -> Option<(W, Vec<K>)>
+    {
+        self.try_shorted_path(from, to).ok().flatten()
+    }
+
+    pub(crate) fn try_shorted_path(&self, from: K, to: K)
+        -> Result<Option<(W, Vec<K>)>, PathError<K>>
+    {
+        let (src, dst) = match (self.get_node_index(&from), self.get_node_index(&to))
+        {
+            (Some(src), Some(dst)) => (src, dst),
+            _ => return Ok(None)
+        };
+
+        let (distance, parent) = self.dijkstra(src, Some(dst));
+        match distance[dst]
+        {
+            TentativeWeight::Number(n) => Ok(Some((n, self.build_path(&parent, dst)))),
+            TentativeWeight::Overflow => Err(PathError::Overflow(to)),
+            TentativeWeight::Underflow => unreachable!("non-negative weights can't underflow"),
+            TentativeWeight::Infinite => Ok(None)
+        }
+    }
+
+    // Nodes whose cost overflows are left out like unreachable ones,
+    // `try_shortest_paths_from` reports them instead
+    pub(crate) fn shortest_paths_from(&self, from: K) -> HashMap<K, (W, Vec<K>)>
+    {
+        match self.get_node_index(&from)
+        {
+            Some(src) => self.paths_from(src),
+            None => HashMap::new()
+        }
+    }
+
+    pub(crate) fn try_shortest_paths_from(&self, from: K)
+        -> Result<PathTree<K, W>, PathError<K>>
+    {
+        let src = match self.get_node_index(&from)
+        {
+            Some(i) => i,
+            None => return Ok(HashMap::new())
+        };
+
+        let (distance, parent) = self.dijkstra(src, None);
+        self.check_overflow(&distance)?;
+        Ok(self.collect_paths(&distance, &parent))
+    }
+
+    // Leaves out overflowing costs like `shortest_paths_from`
+    pub(crate) fn all_pairs_shortest_paths(&self) -> HashMap<K, HashMap<K, W>>
+    {
+        (0..self.nodes.len())
+            .map(|src| (self.nodes[src].clone(), self.distances_from(src)))
+            .collect()
+    }
+
+    pub(crate) fn try_all_pairs_shortest_paths(&self)
+        -> Result<HashMap<K, HashMap<K, W>>, PathError<K>>
+    {
+        (0..self.nodes.len()).map(|src|
+        {
+            let (distance, _) = self.dijkstra(src, None);
+            self.check_overflow(&distance)?;
+            Ok((self.nodes[src].clone(), self.collect_distances(&distance)))
+        }).collect()
+    }
+
+    pub(crate) fn paths_from(&self, src: usize) -> HashMap<K, (W, Vec<K>)>
+    {
+        let (distance, parent) = self.dijkstra(src, None);
+        self.collect_paths(&distance, &parent)
+    }
+
+    pub(crate) fn distances_from(&self, src: usize) -> HashMap<K, W>
+    {
+        let (distance, _) = self.dijkstra(src, None);
+        self.collect_distances(&distance)
+    }
+
+    fn collect_paths(&self, distance: &[TentativeWeight<W>], parent: &[Option<usize>])
+        -> HashMap<K, (W, Vec<K>)>
+    {
+        distance.iter().enumerate().filter_map(|(i, d)| match d
+        {
+            TentativeWeight::Number(n) =>
+                Some((self.nodes[i].clone(), (*n, self.build_path(parent, i)))),
+            _ => None
+        }).collect()
+    }
+
+    fn collect_distances(&self, distance: &[TentativeWeight<W>]) -> HashMap<K, W>
+    {
+        distance.iter().enumerate().filter_map(|(i, d)| match d
+        {
+            TentativeWeight::Number(n) => Some((self.nodes[i].clone(), *n)),
+            _ => None
+        }).collect()
+    }
+
+    // The first node that can be reached, but not for a cost that fits
+    fn check_overflow(&self, distance: &[TentativeWeight<W>]) -> Result<(), PathError<K>>
+    {
+        match distance.iter().position(|d| *d == TentativeWeight::Overflow)
+        {
+            Some(i) => Err(PathError::Overflow(self.nodes[i].clone())),
+            None => Ok(())
+        }
+    }
+
+    // Runs until `stop` is settled, or over the whole graph when there is none
+    pub(crate) fn dijkstra(&self, src: usize, stop: Option<usize>)
+        -> (Vec<TentativeWeight<W>>, Vec<Option<usize>>)
+    {
+        let count = self.nodes.len();
+        let mut distance: Vec<TentativeWeight<W>> = vec![TentativeWeight::Infinite; count];
+        distance[src] = TentativeWeight::Number(W::zero());
+        let mut parent = vec![None; count];
+
+        // Stale entries are skipped when popped instead of decreasing keys
+        let mut open = BinaryHeap::new();
+        open.push(Reverse((TentativeWeight::Number(W::zero()), src)));
 
-        if src.is_some() && dst.is_some()
+        while let Some(Reverse((dist, u))) = open.pop()
         {
-            let (src, dst) = (src.unwrap(), dst.unwrap());
+            if dist > distance[u]
+            {
+                continue;
+            }
 
-            let mut distance: Vec<TentativeWeight> =
-                vec![TentativeWeight::Infinite; self.nodes.len()];
-            distance[src] = TentativeWeight::Number(0);
-            let mut open: Vec<usize> = 
-                (0..self.nodes.len()).into_iter().collect();
-            let mut parent = vec![None; self.nodes.len()];
-            let mut found = false;
-            while !open.is_empty()
+            if Some(u) == stop
             {
-                let u = min_index(&distance, &open);
-                let u = open.remove(u);
+                break;
+            }
 
-                if u == dst
+            for e in self.adjacent(u)
+            {
+                let new_distance = dist.add(e.weight);
+                if new_distance < distance[e.node]
                 {
-                    found = true;
-                    break;
+                    distance[e.node] = new_distance.clone();
+                    parent[e.node] = Some(u);
+                    open.push(Reverse((new_distance, e.node)));
                 }
+            }
+        }
+        (distance, parent)
+    }
+}
 
-                let dist = distance[u].clone();
+fn build_path<K: Clone>(nodes: &[K], parent: &[Option<usize>], dst: usize) -> Vec<K>
+{
+    let mut path = vec![nodes[dst].clone()];
+    let mut p = dst;
+    while let Some(prev) = parent[p]
+    {
+        path.push(nodes[prev].clone());
+        p = prev;
+    }
+    path.reverse();
+    path
+}
 
-                for e in &self.adjacency_list[u]
-                {
-                    let new_distance = match dist
-                    {
-                        TentativeWeight::Number(n) =>
-                            TentativeWeight::Number(n + e.weight),
-                        _ => TentativeWeight::Infinite
-                    };
-                    let old_distance = distance[e.node].clone();
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::test_support::{keys, random_graph, Lcg};
+    use std::convert::TryFrom;
 
-                    if new_distance < old_distance
+    // The previous linear-scan implementation, kept to check the heap version against
+    fn linear_scan_cost(g: &InternetOfThings, from: KeyType, to: KeyType) -> Option<u32>
+    {
+        let src = g.get_node_index(&from)?;
+        let dst = g.get_node_index(&to)?;
+        let mut distance = vec![TentativeWeight::Infinite; g.nodes.len()];
+        distance[src] = TentativeWeight::Number(0);
+        let mut open: Vec<usize> = (0..g.nodes.len()).collect();
+        while !open.is_empty()
+        {
+            let (i, _) =
+                open.iter().enumerate().min_by_key(|(_, n)| distance[**n].clone()).unwrap();
+            let u = open.remove(i);
+            if u == dst
+            {
+                break;
+            }
+            if let TentativeWeight::Number(n) = distance[u].clone()
+            {
+                for e in &g.adjacency_list[u]
+                {
+                    let new_distance = TentativeWeight::Number(n + e.weight);
+                    if new_distance < distance[e.node]
                     {
                         distance[e.node] = new_distance;
-                        parent[e.node] = Some(u);
                     }
                 }
             }
-            if found
+        }
+        match distance[dst]
+        {
+            TentativeWeight::Number(n) => Some(n),
+            _ => None
+        }
+    }
+
+    fn path_cost(g: &InternetOfThings, path: &[KeyType]) -> u32
+    {
+        path.windows(2)
+            .map(|w|
             {
-                let mut path = vec![];
-                let mut p = parent[dst].unwrap();
-                path.push(self.nodes[dst].clone());
-                while p != src
-                {
-                    path.push(self.nodes[p].clone());
-                    p = parent[p].unwrap();
-                }
-                path.push(self.nodes[src].clone());
+                let from = g.get_node_index(&w[0]).unwrap();
+                let to = g.get_node_index(&w[1]).unwrap();
+                g.adjacency_list[from]
+                    .iter()
+                    .filter(|e| e.node == to)
+                    .map(|e| e.weight)
+                    .min()
+                    .unwrap()
+            })
+            .sum()
+    }
 
-                path.reverse();
-                let cost = match distance[dst]
+    #[test]
+    fn shortest_path_simple()
+    {
+        let mut g = InternetOfThings::new();
+        g.set_nodes(vec![1, 2, 3, 4]);
+        g.set_edges(1, vec![(1, 2), (10, 4)]);
+        g.set_edges(2, vec![(1, 3)]);
+        g.set_edges(3, vec![(1, 4)]);
+        assert_eq!(g.shorted_path(1, 4), Some((3, vec![1, 2, 3, 4])));
+        assert_eq!(g.shorted_path(4, 1), None);
+        assert_eq!(g.shorted_path(1, 1), Some((0, vec![1])));
+        assert_eq!(g.shorted_path(1, 99), None);
+    }
+
+    #[test]
+    fn shortest_path_matches_linear_scan()
+    {
+        let mut rng = Lcg(42);
+        for _ in 0..20
+        {
+            let g = random_graph(&mut rng, 60, 4);
+            for (from, to) in rng.pairs(60, 30)
+            {
+                let expected = linear_scan_cost(&g, from, to);
+                let actual = g.shorted_path(from, to);
+                assert_eq!(actual.as_ref().map(|r| r.0), expected);
+                if let Some((cost, path)) = actual
                 {
-                    TentativeWeight::Number(n) => n,
-                    _ => 0
-                };
-                Some((cost, path))
-            } else {
-                None
+                    assert_eq!(path.first(), Some(&from));
+                    assert_eq!(path.last(), Some(&to));
+                    assert_eq!(path_cost(&g, &path), cost);
+                }
             }
-        } else {
-            None
         }
     }
 
-    fn get_node_index(&self, node: KeyType) -> Option<usize>
+    #[test]
+    fn add_and_remove_nodes()
     {
-        self.nodes.iter().position(|n| n == &node)
+        let mut g = InternetOfThings::new();
+        g.set_nodes(vec![1, 2, 3]);
+        assert!(g.contains_node(2));
+        assert!(!g.contains_node(4));
+        assert!(g.add_node(4));
+        assert!(!g.add_node(4));
+        assert_eq!(g.nodes(), 4);
+
+        g.set_edges(1, vec![(1, 2), (5, 4)]);
+        g.set_edges(2, vec![(1, 3)]);
+        g.set_edges(3, vec![(1, 4)]);
+        assert_eq!(g.shorted_path(1, 4), Some((3, vec![1, 2, 3, 4])));
+
+        assert!(g.remove_node(2));
+        assert!(!g.remove_node(2));
+        assert!(!g.contains_node(2));
+        assert_eq!(g.nodes(), 3);
+        assert_eq!(g.edges(), 2);
+        assert_eq!(g.shorted_path(1, 4), Some((5, vec![1, 4])));
+        assert_eq!(g.shorted_path(3, 4), Some((1, vec![3, 4])));
+        assert_eq!(g.shorted_path(1, 3), None);
     }
 
-    fn connected_r(&self, from: usize, degree: usize) -> HashSet<usize>
+    #[test]
+    fn set_edges_adds_unknown_source()
     {
-        if degree > 0
-        {
-            self.adjacency_list[from]
-                .iter()
-                .flat_map(|e|
-                {
-                    let mut set = self.connected_r(e.node, degree - 1);
-                    set.insert(e.node);
-                    set
-                }).collect()
-        } else {
-            HashSet::new()
-        }
+        let mut g = InternetOfThings::new();
+        g.set_nodes(vec![1]);
+        g.set_edges(7, vec![(2, 1)]);
+        assert!(g.contains_node(7));
+        assert_eq!(g.shorted_path(7, 1), Some((2, vec![7, 1])));
     }
-}
 
-fn min_index(weights: &Vec<TentativeWeight>, nodes: &Vec<usize>) -> usize
-{
-    let mut min_weight = (weights[0].clone(), 0);
-    for node in nodes.iter()
+    #[test]
+    fn shortest_paths_from_source()
     {
-        if let Some(n) = weights.get(*node)
+        let mut g = InternetOfThings::new();
+        g.set_nodes(vec![1, 2, 3, 4, 5]);
+        g.set_edges(1, vec![(1, 2), (4, 3)]);
+        g.set_edges(2, vec![(1, 3)]);
+        g.set_edges(3, vec![(2, 4)]);
+
+        let tree = g.shortest_paths_from(1);
+        assert_eq!(tree.len(), 4);
+        assert_eq!(tree[&1], (0, vec![1]));
+        assert_eq!(tree[&3], (2, vec![1, 2, 3]));
+        assert_eq!(tree[&4], (4, vec![1, 2, 3, 4]));
+        assert!(!tree.contains_key(&5));
+        assert!(g.shortest_paths_from(99).is_empty());
+    }
+
+    #[test]
+    fn all_pairs_matches_single_pair()
+    {
+        let mut rng = Lcg(7);
+        let g = random_graph(&mut rng, 40, 3);
+        let matrix = g.all_pairs_shortest_paths();
+        assert_eq!(matrix.len(), 40);
+        for from in keys(40)
         {
-            if n < &min_weight.0
+            for to in keys(40)
             {
-                min_weight = ((&weights[*node]).clone(), node.clone())
+                let expected = g.shorted_path(from, to).map(|r| r.0);
+                assert_eq!(matrix[&from].get(&to).copied(), expected);
             }
         }
     }
-    return min_weight.1;
-}
\ No newline at end of file
+
+    #[test]
+    fn overflow_is_reported()
+    {
+        let mut g = InternetOfThings::new();
+        g.set_nodes(vec![1, 2, 3, 4]);
+        g.set_edges(1, vec![(u32::MAX, 2)]);
+        g.set_edges(2, vec![(1, 3)]);
+        g.set_edges(3, vec![(1, 4)]);
+        assert_eq!(g.try_shorted_path(1, 2), Ok(Some((u32::MAX, vec![1, 2]))));
+        assert_eq!(g.try_shorted_path(1, 3), Err(PathError::Overflow(3)));
+        assert_eq!(g.try_shorted_path(1, 4), Err(PathError::Overflow(4)));
+        // The plain variants can't tell overflow from unreachable
+        assert_eq!(g.shorted_path(1, 4), None);
+        assert_eq!(g.shorted_path(1, 2), Some((u32::MAX, vec![1, 2])));
+        let tree = g.shortest_paths_from(1);
+        assert_eq!(tree.keys().copied().collect::<HashSet<_>>(), vec![1, 2].into_iter().collect());
+        assert_eq!(g.all_pairs_shortest_paths()[&1].len(), 2);
+        assert_eq!(g.try_shortest_paths_from(1), Err(PathError::Overflow(3)));
+        assert_eq!(g.try_all_pairs_shortest_paths(), Err(PathError::Overflow(3)));
+        assert_eq!(g.try_shortest_paths_from(2).map(|t| t.len()), Ok(3));
+
+        // A cheaper route that fits wins over an overflowing one
+        g.set_edges(1, vec![(u32::MAX, 2), (5, 4)]);
+        assert_eq!(g.try_shorted_path(1, 4), Ok(Some((5, vec![1, 4]))));
+        g.set_edges(1, vec![(u32::MAX - 2, 2)]);
+        assert_eq!(g.try_shortest_paths_from(1), Ok(g.shortest_paths_from(1)));
+        assert_eq!(g.try_all_pairs_shortest_paths(), Ok(g.all_pairs_shortest_paths()));
+    }
+
+    #[test]
+    fn string_keys_and_float_weights()
+    {
+        let w = |x| NonNegativeF64::new(x).unwrap();
+        let mut g: Graph<String, NonNegativeF64> = Graph::new();
+        g.set_nodes(vec!["aa:01".to_string(), "aa:02".to_string(), "aa:03".to_string()]);
+        g.set_edges(
+            "aa:01".to_string(),
+            vec![(w(0.5), "aa:02".to_string()), (w(2.0), "aa:03".to_string())]
+        );
+        g.set_edges("aa:02".to_string(), vec![(w(0.25), "aa:03".to_string())]);
+
+        let (cost, path) = g.shorted_path("aa:01".to_string(), "aa:03".to_string()).unwrap();
+        assert_eq!(cost, w(0.75));
+        assert_eq!(path, vec!["aa:01", "aa:02", "aa:03"]);
+
+        g.set_edges("aa:02".to_string(), vec![(w(f64::MAX), "aa:03".to_string())]);
+        g.set_edges("aa:01".to_string(), vec![(w(f64::MAX), "aa:02".to_string())]);
+        assert_eq!(
+            g.try_shorted_path("aa:01".to_string(), "aa:03".to_string()),
+            Err(PathError::Overflow("aa:03".to_string()))
+        );
+    }
+
+    #[test]
+    fn non_negative_floats()
+    {
+        assert_eq!(NonNegativeF64::new(-0.5), None);
+        assert_eq!(NonNegativeF64::new(f64::NAN), None);
+        assert_eq!(NonNegativeF64::new(-0.0).map(|w| w.get().to_bits()), Some(0));
+        assert_eq!(NonNegativeF64::try_from(-2.0), Err(NegativeWeight(-2.0)));
+
+        // Signed floats still work with Bellman-Ford
+        let mut g: Graph<KeyType, TotalF64> = Graph::new();
+        g.set_nodes(vec![1, 2, 3]);
+        g.set_edges(1, vec![(TotalF64(2.0), 2), (TotalF64(5.0), 3)]);
+        g.set_edges(3, vec![(TotalF64(-4.0), 2)]);
+        assert_eq!(g.bellman_ford(1, 2), Ok(Some((TotalF64(1.0), vec![1, 3, 2]))));
+    }
+
+    #[test]
+    fn composite_weights()
+    {
+        // (latency, hops): the lowest latency wins, ties go to fewer hops
+        let mut g: Graph<KeyType, (u32, u8)> = Graph::new();
+        g.set_nodes(vec![1, 2, 3, 4]);
+        g.set_edges(1, vec![((5, 1), 2), ((10, 1), 4)]);
+        g.set_edges(2, vec![((5, 1), 4), ((1, 1), 3)]);
+        g.set_edges(3, vec![((4, 1), 4)]);
+        assert_eq!(g.shorted_path(1, 4), Some(((10, 1), vec![1, 4])));
+    }
+
+    #[test]
+    fn strict_and_lenient_edges()
+    {
+        let mut g = InternetOfThings::new();
+        g.set_nodes(vec![1, 2]);
+        let edges = vec![(1, 2), (2, 7), (3, 8), (4, 7)];
+        assert_eq!(g.set_edges_strict(1, edges), Err(UnknownNodes(vec![7, 8])));
+        assert_eq!(g.edges(), 0);
+        assert_eq!(g.set_edges_strict(1, vec![(1, 2)]), Ok(()));
+        assert_eq!(g.edges(), 1);
+
+        g.set_edges_lenient(2, vec![(1, 7), (1, 8)]);
+        assert!(g.contains_node(7));
+        assert!(g.contains_node(8));
+        assert_eq!(g.shorted_path(1, 8), Some((2, vec![1, 2, 8])));
+    }
+
+    #[test]
+    fn single_edge_edits()
+    {
+        let mut g = InternetOfThings::new();
+        g.set_nodes(vec![1, 2, 3]);
+        assert_eq!(g.add_edge(1, 2, 4), Ok(()));
+        assert_eq!(g.add_edge(2, 3, 4), Ok(()));
+        assert_eq!(g.add_edge(1, 9, 4), Err(UnknownNodes(vec![9])));
+        assert_eq!(g.add_edge(8, 9, 4), Err(UnknownNodes(vec![8, 9])));
+        assert_eq!(g.shorted_path(1, 3), Some((8, vec![1, 2, 3])));
+
+        assert_eq!(g.update_weight(2, 3, 1), Some(4));
+        assert_eq!(g.update_weight(3, 2, 1), None);
+        assert_eq!(g.shorted_path(1, 3), Some((5, vec![1, 2, 3])));
+
+        assert_eq!(g.remove_edge(1, 2), Some(4));
+        assert_eq!(g.remove_edge(1, 2), None);
+        assert_eq!(g.shorted_path(1, 3), None);
+        assert_eq!(g.edges(), 1);
+    }
+
+    fn undirected(multiplicity: Multiplicity) -> InternetOfThings
+    {
+        let mode = GraphMode::new(Direction::Undirected, multiplicity);
+        let mut g = InternetOfThings::with_mode(mode);
+        g.set_nodes(vec![1, 2, 3]);
+        g
+    }
+
+    #[test]
+    fn undirected_edges_stay_symmetric()
+    {
+        let mut g = undirected(Multiplicity::Multi);
+        g.set_edges(1, vec![(4, 2), (1, 3)]);
+        assert_eq!(g.edges(), 2);
+        assert_eq!(g.shorted_path(2, 3), Some((5, vec![2, 1, 3])));
+
+        // Replacing node 1's edges drops their mirrors as well
+        g.set_edges(1, vec![(2, 2)]);
+        assert_eq!(g.edges(), 1);
+        assert_eq!(g.shorted_path(3, 1), None);
+        assert_eq!(g.shorted_path(2, 1), Some((2, vec![2, 1])));
+
+        assert_eq!(g.update_weight(2, 1, 9), Some(2));
+        assert_eq!(g.shorted_path(1, 2), Some((9, vec![1, 2])));
+        assert_eq!(g.remove_edge(2, 1), Some(9));
+        assert_eq!(g.edges(), 0);
+        assert_eq!(g.shorted_path(1, 2), None);
+
+        g.add_edge(3, 3, 1).unwrap();
+        assert_eq!(g.edges(), 1);
+    }
+
+    #[test]
+    fn multi_graph_uses_cheapest_parallel_edge()
+    {
+        let mut g = undirected(Multiplicity::Multi);
+        g.add_edge(1, 2, 7).unwrap();
+        g.add_edge(2, 1, 3).unwrap();
+        g.add_edge(1, 2, 5).unwrap();
+        assert_eq!(g.edges(), 3);
+        assert_eq!(g.shorted_path(1, 2), Some((3, vec![1, 2])));
+
+        assert_eq!(g.remove_edge(1, 2), Some(7));
+        assert_eq!(g.edges(), 2);
+        assert_eq!(g.shorted_path(2, 1), Some((3, vec![2, 1])));
+    }
+
+    #[test]
+    fn simple_graph_replaces_edges()
+    {
+        let mut g = undirected(Multiplicity::Simple);
+        g.add_edge(1, 2, 7).unwrap();
+        g.add_edge(2, 1, 3).unwrap();
+        assert_eq!(g.edges(), 1);
+        assert_eq!(g.shorted_path(1, 2), Some((3, vec![1, 2])));
+
+        let mode = GraphMode::new(Direction::Directed, Multiplicity::Simple);
+        let mut directed = InternetOfThings::with_mode(mode);
+        directed.set_nodes(vec![1, 2]);
+        directed.set_edges(1, vec![(4, 2), (6, 2)]);
+        assert_eq!(directed.edges(), 1);
+        assert_eq!(directed.shorted_path(1, 2), Some((6, vec![1, 2])));
+        assert_eq!(directed.shorted_path(2, 1), None);
+    }
+}
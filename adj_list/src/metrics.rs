@@ -0,0 +1,622 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::hash::Hash;
+
+use super::traversal::{forward_to_view, GraphView};
+use super::{Edge, NonNegative, Numeric, TentativeWeight};
+
+forward_to_view!
+{
+    [K: Hash + Eq + Clone, W: Numeric] (K, W)
+    fn closeness_centrality(&self) -> HashMap<K, f64>;
+}
+
+forward_to_view!
+{
+    [K: Hash + Eq + Clone, W: NonNegative] (K, W)
+    fn betweenness_centrality(&self) -> HashMap<K, f64>;
+    fn eccentricity(&self) -> HashMap<K, Option<W>>;
+    fn diameter(&self) -> Option<W>;
+}
+
+forward_to_view!
+{
+    [K: Hash + Eq + Clone, W: Clone] (K, W)
+    fn hop_closeness_centrality(&self) -> HashMap<K, f64>;
+    fn hop_betweenness_centrality(&self) -> HashMap<K, f64>;
+    fn hop_eccentricity(&self) -> HashMap<K, Option<usize>>;
+    fn hop_diameter(&self) -> Option<usize>;
+    fn in_degree(&self) -> HashMap<K, usize>;
+    fn out_degree(&self) -> HashMap<K, usize>;
+    fn page_rank(&self, damping: f64, iterations: usize) -> HashMap<K, f64>;
+    fn articulation_points(&self) -> Vec<K>;
+    fn bridges(&self) -> Vec<(K, K)>;
+}
+
+// Distances here follow the edge weights. Costs that overflow the weight type
+// count as unreachable.
+impl<'a, K: Hash + Eq + Clone, W: Numeric> GraphView<'a, K, W>
+{
+    // Wasserman-Faust closeness, scaled by the share of nodes a node can reach
+    // so it stays comparable on disconnected graphs. 0 for isolated nodes.
+    pub(crate) fn closeness_centrality(&self) -> HashMap<K, f64>
+    {
+        self.by_key((0..self.nodes.len()).map(|src| self.closeness_of(src, weighted)).collect())
+    }
+}
+
+impl<'a, K: Hash + Eq + Clone, W: NonNegative> GraphView<'a, K, W>
+{
+    // Brandes' algorithm over weighted shortest paths, unnormalised.
+    // Undirected graphs count each pair once.
+    pub(crate) fn betweenness_centrality(&self) -> HashMap<K, f64>
+    {
+        let dependencies = (0..self.nodes.len()).map(|src| self.dependencies_from(src, weighted));
+        self.sum_betweenness(dependencies)
+    }
+
+    // Cost of reaching the farthest node; None when some node can't be reached
+    pub(crate) fn eccentricity(&self) -> HashMap<K, Option<W>>
+    {
+        self.by_key((0..self.nodes.len()).map(|src| self.eccentricity_of(src, weighted)).collect())
+    }
+
+    // None for empty graphs and ones where not every node reaches every other
+    pub(crate) fn diameter(&self) -> Option<W>
+    {
+        diameter(self.eccentricity().into_values())
+    }
+}
+
+// The same metrics with every edge counted as one hop, for any weight type
+impl<'a, K: Hash + Eq + Clone, W: Clone> GraphView<'a, K, W>
+{
+    pub(crate) fn hop_closeness_centrality(&self) -> HashMap<K, f64>
+    {
+        self.by_key((0..self.nodes.len()).map(|src| self.closeness_of(src, hop)).collect())
+    }
+
+    pub(crate) fn hop_betweenness_centrality(&self) -> HashMap<K, f64>
+    {
+        let dependencies = (0..self.nodes.len()).map(|src| self.dependencies_from(src, hop));
+        self.sum_betweenness(dependencies)
+    }
+
+    pub(crate) fn hop_eccentricity(&self) -> HashMap<K, Option<usize>>
+    {
+        self.by_key((0..self.nodes.len()).map(|src| self.eccentricity_of(src, hop)).collect())
+    }
+
+    pub(crate) fn hop_diameter(&self) -> Option<usize>
+    {
+        diameter(self.hop_eccentricity().into_values())
+    }
+}
+
+impl<'a, K: Hash + Eq + Clone, W: Clone> GraphView<'a, K, W>
+{
+    pub(crate) fn in_degree(&self) -> HashMap<K, usize>
+    {
+        let mut degree = vec![0; self.nodes.len()];
+        for u in 0..self.nodes.len()
+        {
+            for e in self.adjacent(u)
+            {
+                degree[e.node] += 1;
+            }
+        }
+        self.by_key(degree)
+    }
+
+    pub(crate) fn out_degree(&self) -> HashMap<K, usize>
+    {
+        self.by_key((0..self.nodes.len()).map(|u| self.adjacent(u).len()).collect())
+    }
+
+    // Power iteration; rank of nodes without outgoing edges is spread evenly
+    pub(crate) fn page_rank(&self, damping: f64, iterations: usize) -> HashMap<K, f64>
+    {
+        let n = self.nodes.len();
+        if n == 0
+        {
+            return HashMap::new();
+        }
+
+        let mut rank = vec![1.0 / n as f64; n];
+        for _ in 0..iterations
+        {
+            let dangling: f64 = (0..n)
+                .filter(|u| self.adjacent(*u).is_empty())
+                .map(|u| rank[u])
+                .sum();
+            let base = (1.0 - damping) / n as f64 + damping * dangling / n as f64;
+            let mut next = vec![base; n];
+            for (u, r) in rank.iter().enumerate()
+            {
+                let edges = self.adjacent(u);
+                let share = damping * r / edges.len() as f64;
+                for e in edges
+                {
+                    next[e.node] += share;
+                }
+            }
+            rank = next;
+        }
+        self.by_key(rank)
+    }
+
+    // Nodes whose failure splits the network, ignoring edge directions
+    pub(crate) fn articulation_points(&self) -> Vec<K>
+    {
+        let (points, _) = self.cut_structure();
+        points.into_iter().map(|n| self.nodes[n].clone()).collect()
+    }
+
+    // Links whose failure splits the network, ignoring edge directions
+    pub(crate) fn bridges(&self) -> Vec<(K, K)>
+    {
+        let (_, bridges) = self.cut_structure();
+        bridges.into_iter()
+            .map(|(a, b)| (self.nodes[a].clone(), self.nodes[b].clone()))
+            .collect()
+    }
+
+    pub(crate) fn closeness_of<D: Numeric>(&self, src: usize, cost: impl Fn(&Edge<W>) -> D) -> f64
+    {
+        let n = self.nodes.len();
+        let dag = self.shortest_path_dag(src, cost);
+        let total: f64 = dag.order.iter()
+            .filter_map(|u| match dag.distance[*u]
+            {
+                TentativeWeight::Number(d) => Some(d.to_f64()),
+                _ => None
+            })
+            .sum();
+        let others = dag.order.len() - 1;
+        if total == 0.0 || n < 2
+        {
+            0.0
+        } else {
+            (others as f64 / total) * (others as f64 / (n - 1) as f64)
+        }
+    }
+
+    // What the shortest paths starting at `src` add to each node's betweenness
+    pub(crate) fn dependencies_from<D: NonNegative>(&self, src: usize,
+        cost: impl Fn(&Edge<W>) -> D) -> Vec<f64>
+    {
+        let dag = self.shortest_path_dag(src, cost);
+        let mut dependency = vec![0.0; self.nodes.len()];
+        for w in dag.order.into_iter().rev()
+        {
+            for v in dag.predecessors[w].iter()
+            {
+                dependency[*v] += dag.paths[*v] / dag.paths[w] * (1.0 + dependency[w]);
+            }
+        }
+        dependency[src] = 0.0;
+        dependency
+    }
+
+    // Adds up per-source dependencies in the order given, so callers that
+    // keep source order get the same floating point result
+    pub(crate) fn sum_betweenness(&self, dependencies: impl Iterator<Item = Vec<f64>>)
+        -> HashMap<K, f64>
+    {
+        let mut centrality = vec![0.0; self.nodes.len()];
+        for dependency in dependencies
+        {
+            for (c, d) in centrality.iter_mut().zip(dependency)
+            {
+                *c += d;
+            }
+        }
+        if !self.mode.is_directed()
+        {
+            for c in centrality.iter_mut()
+            {
+                *c /= 2.0;
+            }
+        }
+        self.by_key(centrality)
+    }
+
+    fn eccentricity_of<D: NonNegative>(&self, src: usize, cost: impl Fn(&Edge<W>) -> D)
+        -> Option<D>
+    {
+        let dag = self.shortest_path_dag(src, cost);
+        if dag.order.len() < self.nodes.len()
+        {
+            return None;
+        }
+        // Nodes are settled nearest first
+        match dag.distance[*dag.order.last()?]
+        {
+            TentativeWeight::Number(d) => Some(d),
+            _ => None
+        }
+    }
+
+    // Dijkstra that keeps every shortest path instead of just one, as
+    // Brandes' algorithm needs. Nodes whose cost overflows are left unreached.
+    fn shortest_path_dag<D: NonNegative>(&self, src: usize, cost: impl Fn(&Edge<W>) -> D)
+        -> ShortestPathDag<D>
+    {
+        let n = self.nodes.len();
+        let mut dag = ShortestPathDag
+        {
+            order: vec![],
+            distance: vec![TentativeWeight::Infinite; n],
+            paths: vec![0.0; n],
+            predecessors: vec![vec![]; n]
+        };
+        let mut settled = vec![false; n];
+        dag.distance[src] = TentativeWeight::Number(D::zero());
+        dag.paths[src] = 1.0;
+
+        let mut open = BinaryHeap::new();
+        open.push(Reverse((D::zero(), src)));
+        while let Some(Reverse((dist, u))) = open.pop()
+        {
+            if settled[u]
+            {
+                continue;
+            }
+            settled[u] = true;
+            dag.order.push(u);
+
+            for e in self.adjacent(u)
+            {
+                let new_distance = match dist.checked_add(cost(e))
+                {
+                    Some(d) => d,
+                    None => continue
+                };
+                if TentativeWeight::Number(new_distance) < dag.distance[e.node]
+                {
+                    dag.distance[e.node] = TentativeWeight::Number(new_distance);
+                    dag.paths[e.node] = dag.paths[u];
+                    dag.predecessors[e.node] = vec![u];
+                    open.push(Reverse((new_distance, e.node)));
+                } else if TentativeWeight::Number(new_distance) == dag.distance[e.node]
+                    // Zero-weight edges can lead back to a node settled at the
+                    // same cost, whose successors have already been counted
+                    && !settled[e.node]
+                {
+                    dag.paths[e.node] += dag.paths[u];
+                    dag.predecessors[e.node].push(u);
+                }
+            }
+        }
+        dag
+    }
+
+    // Tarjan's low-link over the undirected view of the graph. In directed
+    // graphs a -> b and b -> a are the same link; in undirected multi-graphs
+    // parallel edges are redundant links and so never bridges.
+    fn cut_structure(&self) -> (Vec<usize>, Vec<(usize, usize)>)
+    {
+        let n = self.nodes.len();
+        let mut multiplicity: HashMap<(usize, usize), usize> = HashMap::new();
+        for (from, e) in self.edge_list().filter(|(from, e)| *from != e.node)
+        {
+            *multiplicity.entry((from.min(e.node), from.max(e.node))).or_default() += 1;
+        }
+        let directed = self.mode.is_directed();
+        let mut links: Vec<(usize, usize)> = multiplicity.keys().copied().collect();
+        links.sort_unstable();
+
+        let mut neighbours: Vec<Vec<(usize, usize)>> = vec![vec![]; n];
+        for (id, (a, b)) in links.iter().enumerate()
+        {
+            neighbours[*a].push((*b, id));
+            neighbours[*b].push((*a, id));
+        }
+
+        let mut discovered = vec![usize::MAX; n];
+        let mut low = vec![0; n];
+        let mut timer = 0;
+        let mut is_point = vec![false; n];
+        let mut bridges = vec![];
+
+        for root in 0..n
+        {
+            if discovered[root] != usize::MAX
+            {
+                continue;
+            }
+            discovered[root] = timer;
+            low[root] = timer;
+            timer += 1;
+            let mut root_children = 0;
+            // (node, link used to get here, next neighbour to look at)
+            let mut stack: Vec<(usize, Option<usize>, usize)> = vec![(root, None, 0)];
+
+            while let Some((u, via, next)) = stack.last_mut()
+            {
+                let (u, via) = (*u, *via);
+                if let Some((v, id)) = neighbours[u].get(*next).copied()
+                {
+                    *next += 1;
+                    if Some(id) == via
+                    {
+                        continue;
+                    }
+                    if discovered[v] == usize::MAX
+                    {
+                        discovered[v] = timer;
+                        low[v] = timer;
+                        timer += 1;
+                        if u == root
+                        {
+                            root_children += 1;
+                        }
+                        stack.push((v, Some(id), 0));
+                    } else {
+                        low[u] = low[u].min(discovered[v]);
+                    }
+                } else {
+                    stack.pop();
+                    if let Some((p, _, _)) = stack.last()
+                    {
+                        let p = *p;
+                        low[p] = low[p].min(low[u]);
+                        if low[u] > discovered[p]
+                            && (directed || multiplicity[&(p.min(u), p.max(u))] == 1)
+                        {
+                            bridges.push((p, u));
+                        }
+                        if p != root && low[u] >= discovered[p]
+                        {
+                            is_point[p] = true;
+                        }
+                    }
+                }
+            }
+            if root_children > 1
+            {
+                is_point[root] = true;
+            }
+        }
+
+        ((0..n).filter(|i| is_point[*i]).collect(), bridges)
+    }
+}
+
+// Edge costs for the weighted and the hop-counting metrics
+pub(crate) fn weighted<W: Copy>(e: &Edge<W>) -> W
+{
+    e.weight
+}
+
+fn hop<W>(_: &Edge<W>) -> usize
+{
+    1
+}
+
+fn diameter<D: Ord>(eccentricities: impl Iterator<Item = Option<D>>) -> Option<D>
+{
+    let mut diameter = None;
+    for e in eccentricities
+    {
+        let e = e?;
+        diameter = Some(match diameter
+        {
+            Some(d) if d > e => d,
+            _ => e
+        });
+    }
+    diameter
+}
+
+// Shortest paths from one source: nodes in the order they were settled,
+// how many shortest paths reach each node and the nodes they come through
+struct ShortestPathDag<D>
+{
+    order: Vec<usize>,
+    distance: Vec<TentativeWeight<D>>,
+    paths: Vec<f64>,
+    predecessors: Vec<Vec<usize>>
+}
+
+#[cfg(test)]
+mod tests
+{
+    use crate::{Direction, Graph, GraphMode, InternetOfThings, KeyType, Multiplicity};
+
+    // Two triangles joined by the link 3 - 4
+    fn bow_tie() -> InternetOfThings
+    {
+        let mode = GraphMode::new(Direction::Undirected, Multiplicity::Multi);
+        let mut g = InternetOfThings::with_mode(mode);
+        g.set_nodes(vec![1, 2, 3, 4, 5, 6]);
+        for (a, b) in [(1, 2), (2, 3), (1, 3), (3, 4), (4, 5), (5, 6), (4, 6)]
+        {
+            g.add_edge(a, b, 1).unwrap();
+        }
+        g
+    }
+
+    fn close(a: f64, b: f64) -> bool
+    {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn degrees()
+    {
+        let mut g = InternetOfThings::new();
+        g.set_nodes(vec![1, 2, 3]);
+        g.set_edges(1, vec![(1, 2), (1, 3)]);
+        g.set_edges(2, vec![(1, 3)]);
+        assert_eq!(g.out_degree()[&1], 2);
+        assert_eq!(g.out_degree()[&3], 0);
+        assert_eq!(g.in_degree()[&3], 2);
+        assert_eq!(g.in_degree()[&1], 0);
+    }
+
+    #[test]
+    fn centrality_on_a_path()
+    {
+        let mode = GraphMode::new(Direction::Undirected, Multiplicity::Multi);
+        let mut g = InternetOfThings::with_mode(mode);
+        g.set_nodes(vec![1, 2, 3]);
+        g.add_edge(1, 2, 1).unwrap();
+        g.add_edge(2, 3, 1).unwrap();
+
+        let betweenness = g.betweenness_centrality();
+        assert!(close(betweenness[&2], 1.0));
+        assert!(close(betweenness[&1], 0.0));
+
+        let closeness = g.closeness_centrality();
+        assert!(close(closeness[&2], 1.0));
+        assert!(close(closeness[&1], 2.0 / 3.0));
+    }
+
+    #[test]
+    fn betweenness_of_bridge_nodes()
+    {
+        let betweenness = bow_tie().betweenness_centrality();
+        // 3 and 4 each sit between their own triangle and the other three nodes
+        assert!(close(betweenness[&3], 6.0));
+        assert!(close(betweenness[&4], 6.0));
+        assert!(close(betweenness[&1], 0.0));
+    }
+
+    #[test]
+    fn page_rank_sums_to_one()
+    {
+        let mut g = InternetOfThings::new();
+        g.set_nodes(vec![1, 2, 3, 4]);
+        g.set_edges(1, vec![(1, 2)]);
+        g.set_edges(2, vec![(1, 3)]);
+        g.set_edges(3, vec![(1, 1), (1, 2)]);
+        let rank = g.page_rank(0.85, 100);
+        assert!(close(rank.values().sum::<f64>(), 1.0));
+        assert!(rank[&2] > rank[&1]);
+        assert!(rank[&4] < rank[&1]);
+    }
+
+    #[test]
+    fn eccentricity_and_diameter()
+    {
+        let g = bow_tie();
+        let eccentricity = g.eccentricity();
+        assert_eq!(eccentricity[&1], Some(3));
+        assert_eq!(eccentricity[&3], Some(2));
+        assert_eq!(g.diameter(), Some(3));
+
+        let mut directed = InternetOfThings::new();
+        directed.set_nodes(vec![1, 2]);
+        directed.set_edges(1, vec![(1, 2)]);
+        assert_eq!(directed.eccentricity()[&1], Some(1));
+        assert_eq!(directed.eccentricity()[&2], None);
+        assert_eq!(directed.diameter(), None);
+        assert_eq!(InternetOfThings::new().diameter(), None);
+    }
+
+    #[test]
+    fn metrics_follow_weights()
+    {
+        // The direct link 1 - 2 costs more than the detour over 3
+        let mode = GraphMode::new(Direction::Undirected, Multiplicity::Multi);
+        let mut g = InternetOfThings::with_mode(mode);
+        g.set_nodes(vec![1, 2, 3]);
+        g.add_edge(1, 2, 10).unwrap();
+        g.add_edge(1, 3, 1).unwrap();
+        g.add_edge(3, 2, 1).unwrap();
+
+        assert!(close(g.betweenness_centrality()[&3], 1.0));
+        assert!(close(g.hop_betweenness_centrality()[&3], 0.0));
+        assert!(close(g.closeness_centrality()[&1], 2.0 / 3.0));
+        assert!(close(g.hop_closeness_centrality()[&1], 1.0));
+        assert_eq!(g.eccentricity()[&1], Some(2));
+        assert_eq!(g.hop_eccentricity()[&1], Some(1));
+        assert_eq!(g.diameter(), Some(2));
+        assert_eq!(g.hop_diameter(), Some(1));
+    }
+
+    #[test]
+    fn betweenness_splits_over_equal_paths()
+    {
+        let mut g = InternetOfThings::new();
+        g.set_nodes(vec![1, 2, 3, 4]);
+        g.set_edges(1, vec![(1, 2), (2, 3)]);
+        g.set_edges(2, vec![(2, 4)]);
+        g.set_edges(3, vec![(1, 4)]);
+        let betweenness = g.betweenness_centrality();
+        assert!(close(betweenness[&2], 0.5));
+        assert!(close(betweenness[&3], 0.5));
+
+        // A cheaper route over 2 takes all of it
+        g.add_edge(2, 4, 1).unwrap();
+        let betweenness = g.betweenness_centrality();
+        assert!(close(betweenness[&2], 1.0));
+        assert!(close(betweenness[&3], 0.0));
+    }
+
+    #[test]
+    fn overflowing_costs_are_unreachable()
+    {
+        let mut g: Graph<KeyType, u8> = Graph::new();
+        g.set_nodes(vec![1, 2, 3]);
+        g.set_edges(1, vec![(200, 2)]);
+        g.set_edges(2, vec![(100, 3)]);
+        assert_eq!(g.eccentricity()[&1], None);
+        assert_eq!(g.hop_eccentricity()[&2], None);
+        assert!(close(g.closeness_centrality()[&1], 0.5 / 200.0));
+        assert!(close(g.betweenness_centrality()[&2], 0.0));
+        assert!(close(g.hop_betweenness_centrality()[&2], 1.0));
+
+        // Hop counts work for weights that aren't numbers
+        let mut pairs: Graph<KeyType, (u32, u32)> = Graph::new();
+        pairs.set_nodes(vec![1, 2]);
+        pairs.set_edges(1, vec![((1, 1), 2)]);
+        pairs.set_edges(2, vec![((1, 1), 1)]);
+        assert_eq!(pairs.hop_diameter(), Some(1));
+        assert_eq!(pairs.diameter(), Some((1, 1)));
+    }
+
+    #[test]
+    fn articulation_points_and_bridges()
+    {
+        let g = bow_tie();
+        let mut points = g.articulation_points();
+        points.sort();
+        assert_eq!(points, vec![3, 4]);
+        assert_eq!(g.bridges(), vec![(3, 4)]);
+
+        // A second link between 3 and 4 removes the bridge, not the cut nodes
+        let mut redundant = bow_tie();
+        redundant.add_edge(4, 3, 2).unwrap();
+        assert!(redundant.bridges().is_empty());
+        let mut doubled = bow_tie();
+        doubled.add_edge(3, 4, 1).unwrap();
+        assert!(doubled.bridges().is_empty());
+        let mut points = doubled.articulation_points();
+        points.sort();
+        assert_eq!(points, vec![3, 4]);
+
+        // Only the doubled link of a chain stops being a bridge
+        let mut chain = InternetOfThings::with_mode(GraphMode::new(Direction::Undirected,
+            Multiplicity::Multi));
+        chain.set_nodes(vec![1, 2, 3]);
+        for (a, b) in [(1, 2), (2, 1), (2, 3)]
+        {
+            chain.add_edge(a, b, 1).unwrap();
+        }
+        assert_eq!(chain.edges(), 3);
+        assert_eq!(chain.bridges(), vec![(2, 3)]);
+        assert_eq!(chain.articulation_points(), vec![2]);
+
+        // Directed edges both ways are one link
+        let mut directed = InternetOfThings::new();
+        directed.set_nodes(vec![1, 2, 3]);
+        directed.set_edges(1, vec![(1, 2)]);
+        directed.set_edges(2, vec![(1, 1), (1, 3)]);
+        let mut bridges: Vec<(KeyType, KeyType)> = directed.bridges();
+        bridges.sort();
+        assert_eq!(bridges, vec![(1, 2), (2, 3)]);
+        assert_eq!(directed.articulation_points(), vec![2]);
+    }
+}
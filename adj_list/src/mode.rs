@@ -0,0 +1,48 @@
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Direction
+{
+    Directed,
+    // Every edge is stored in both directions and kept in sync
+    Undirected
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Multiplicity
+{
+    // At most one edge between two nodes; adding another replaces its weight
+    Simple,
+    // Parallel edges are kept, paths use the cheapest one
+    Multi
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GraphMode
+{
+    pub direction: Direction,
+    pub multiplicity: Multiplicity
+}
+
+impl GraphMode
+{
+    pub fn new(direction: Direction, multiplicity: Multiplicity) -> GraphMode
+    {
+        GraphMode { direction, multiplicity }
+    }
+
+    pub fn is_directed(&self) -> bool
+    {
+        self.direction == Direction::Directed
+    }
+}
+
+impl Default for GraphMode
+{
+    // What the adjacency list has always done
+    fn default() -> GraphMode
+    {
+        GraphMode::new(Direction::Directed, Multiplicity::Multi)
+    }
+}
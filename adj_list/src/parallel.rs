@@ -0,0 +1,163 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::ops::Range;
+use std::thread;
+
+use super::metrics::weighted;
+use super::traversal::{forward_to_view, GraphView};
+use super::{NonNegative, Numeric};
+
+// Splits 0..count into one contiguous range per thread and runs `job` on each,
+// returning the results in range order
+fn in_parallel<T: Send>(count: usize, job: impl Fn(Range<usize>) -> T + Sync) -> Vec<T>
+{
+    let threads = thread::available_parallelism().map_or(1, |n| n.get()).clamp(1, count.max(1));
+    let chunk = count.div_ceil(threads);
+    let job = &job;
+    thread::scope(|s|
+    {
+        let handles: Vec<_> = (0..threads)
+            .map(|t| s.spawn(move || job(t * chunk..((t + 1) * chunk).min(count))))
+            .collect();
+        handles.into_iter().map(|h| h.join().expect("worker thread panicked")).collect()
+    })
+}
+
+fn each_in_parallel<T: Send>(count: usize, job: impl Fn(usize) -> T + Sync) -> Vec<T>
+{
+    in_parallel(count, |range| range.map(&job).collect::<Vec<T>>())
+        .into_iter()
+        .flatten()
+        .collect()
+}
+
+forward_to_view!
+{
+    [K: Hash + Eq + Clone + Send + Sync, W: Clone + Send + Sync] (K, W)
+    fn par_connected_with_distance(&self, sources: Vec<K>, degree: usize)
+        -> HashMap<K, HashMap<K, usize>>;
+}
+
+forward_to_view!
+{
+    [K: Hash + Eq + Clone + Send + Sync, W: Numeric + Send + Sync] (K, W)
+    fn par_closeness_centrality(&self) -> HashMap<K, f64>;
+}
+
+forward_to_view!
+{
+    [K: Hash + Eq + Clone + Send + Sync, W: NonNegative + Send + Sync] (K, W)
+    fn par_betweenness_centrality(&self) -> HashMap<K, f64>;
+    fn par_shortest_paths_from(&self, sources: Vec<K>) -> HashMap<K, HashMap<K, (W, Vec<K>)>>;
+    fn par_all_pairs_shortest_paths(&self) -> HashMap<K, HashMap<K, W>>;
+}
+
+impl<'a, K: Hash + Eq + Clone + Send + Sync, W: Clone + Send + Sync> GraphView<'a, K, W>
+{
+    // Unknown sources are left out of the result
+    pub(crate) fn par_connected_with_distance(&self, sources: Vec<K>, degree: usize)
+        -> HashMap<K, HashMap<K, usize>>
+    {
+        let found = each_in_parallel(sources.len(), |i|
+        {
+            self.connected_with_distance(sources[i].clone(), degree)
+        });
+        sources.into_iter()
+            .zip(found)
+            .filter_map(|(source, reach)| reach.map(|r| (source, r)))
+            .collect()
+    }
+}
+
+impl<'a, K: Hash + Eq + Clone + Send + Sync, W: Numeric + Send + Sync> GraphView<'a, K, W>
+{
+    pub(crate) fn par_closeness_centrality(&self) -> HashMap<K, f64>
+    {
+        self.by_key(each_in_parallel(self.nodes.len(), |src| self.closeness_of(src, weighted)))
+    }
+}
+
+impl<'a, K: Hash + Eq + Clone + Send + Sync, W: NonNegative + Send + Sync> GraphView<'a, K, W>
+{
+    // Threads only compute the per-source dependencies; they are added up in
+    // source order so the result is the same as the sequential one
+    pub(crate) fn par_betweenness_centrality(&self) -> HashMap<K, f64>
+    {
+        let dependencies = each_in_parallel(self.nodes.len(), |src|
+        {
+            self.dependencies_from(src, weighted)
+        });
+        self.sum_betweenness(dependencies.into_iter())
+    }
+
+    pub(crate) fn par_shortest_paths_from(&self, sources: Vec<K>)
+        -> HashMap<K, HashMap<K, (W, Vec<K>)>>
+    {
+        let paths = each_in_parallel(sources.len(), |i|
+        {
+            self.shortest_paths_from(sources[i].clone())
+        });
+        sources.into_iter().zip(paths).collect()
+    }
+
+    pub(crate) fn par_all_pairs_shortest_paths(&self) -> HashMap<K, HashMap<K, W>>
+    {
+        let rows = each_in_parallel(self.nodes.len(), |src| self.distances_from(src));
+        self.by_key(rows)
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use crate::test_support::{keys, random_graph, random_undirected, Lcg};
+    use crate::InternetOfThings;
+    use std::collections::HashMap;
+
+    #[test]
+    fn multi_source_searches_match_sequential()
+    {
+        let mut rng = Lcg(99);
+        for nodes in [1, 7, 60]
+        {
+            let g = random_graph(&mut rng, nodes, 3);
+            let mut sources = keys(nodes);
+            sources.push(12345);
+
+            let expected: HashMap<_, _> =
+                sources.iter().map(|s| (*s, g.shortest_paths_from(*s))).collect();
+            assert_eq!(g.par_shortest_paths_from(sources.clone()), expected);
+            assert_eq!(g.par_all_pairs_shortest_paths(), g.all_pairs_shortest_paths());
+
+            let expected: HashMap<_, _> = sources
+                .iter()
+                .filter_map(|s| g.connected_with_distance(*s, 3).map(|r| (*s, r)))
+                .collect();
+            assert_eq!(g.par_connected_with_distance(sources, 3), expected);
+        }
+    }
+
+    #[test]
+    fn centrality_matches_sequential()
+    {
+        let mut rng = Lcg(5);
+        let g = random_graph(&mut rng, 80, 2);
+        assert_eq!(g.par_closeness_centrality(), g.closeness_centrality());
+
+        let undirected = random_undirected(&mut rng, 80, 80, 1);
+
+        for graph in [&g, &undirected]
+        {
+            assert_eq!(graph.par_betweenness_centrality(), graph.betweenness_centrality());
+        }
+    }
+
+    #[test]
+    fn empty_graph()
+    {
+        let g = InternetOfThings::new();
+        assert!(g.par_all_pairs_shortest_paths().is_empty());
+        assert!(g.par_betweenness_centrality().is_empty());
+        assert!(g.par_connected_with_distance(vec![], 2).is_empty());
+    }
+}
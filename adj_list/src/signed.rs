@@ -0,0 +1,220 @@
+use std::collections::VecDeque;
+use std::hash::Hash;
+
+use super::traversal::{forward_to_view, GraphView};
+use super::{Graph, KeyType, PathError, TentativeWeight, Weight};
+
+// Edge weights may be negative here (link credits, discounts), so paths are
+// found with Bellman-Ford instead of Dijkstra.
+pub type SignedInternetOfThings = Graph<KeyType, i64>;
+
+forward_to_view!
+{
+    [K: Hash + Eq + Clone, W: Weight] (K, W)
+    fn bellman_ford(&self, from: K, to: K) -> Result<Option<(W, Vec<K>)>, PathError<K>>;
+}
+
+impl<'a, K: Hash + Eq + Clone, W: Weight> GraphView<'a, K, W>
+{
+    // Unlike `shorted_path` this copes with negative weights, at O(V * E).
+    // Overflow and negative cycles only count when they affect `to`. A cost
+    // that doesn't fit is kept only while no route that fits is cheaper, so it
+    // is reported after the last round. Every partial sum of a path has to fit,
+    // and a negative cycle that underflows before the last round is reported as
+    // `Underflow`.
+    pub(crate) fn bellman_ford(&self, from: K, to: K) -> Result<Option<(W, Vec<K>)>, PathError<K>>
+    {
+        let (src, dst) = match (self.get_node_index(&from), self.get_node_index(&to))
+        {
+            (Some(src), Some(dst)) => (src, dst),
+            _ => return Ok(None)
+        };
+
+        let mut distance = vec![TentativeWeight::Infinite; self.nodes.len()];
+        let mut parent: Vec<Option<usize>> = vec![None; self.nodes.len()];
+        distance[src] = TentativeWeight::Number(W::zero());
+
+        // After |V| - 1 rounds every shortest path is settled, so anything
+        // that still relaxes in the final round sits on or behind a negative cycle
+        let mut relaxed = vec![];
+        for _ in 0..self.nodes.len()
+        {
+            relaxed.clear();
+            for u in 0..self.nodes.len()
+            {
+                if distance[u] == TentativeWeight::Infinite
+                {
+                    continue;
+                }
+                for e in self.adjacent(u)
+                {
+                    let new_distance = distance[u].add(e.weight);
+                    if new_distance < distance[e.node]
+                    {
+                        distance[e.node] = new_distance;
+                        parent[e.node] = Some(u);
+                        relaxed.push(e.node);
+                    }
+                }
+            }
+            if relaxed.is_empty()
+            {
+                break;
+            }
+        }
+
+        if let Some(node) = self.reaching(&relaxed, dst)
+        {
+            return Err(PathError::NegativeCycle(self.find_cycle(&parent, node)));
+        }
+
+        match distance[dst]
+        {
+            TentativeWeight::Number(cost) => Ok(Some((cost, self.build_path(&parent, dst)))),
+            TentativeWeight::Overflow => Err(PathError::Overflow(to)),
+            TentativeWeight::Underflow => Err(PathError::Underflow(to)),
+            TentativeWeight::Infinite => Ok(None)
+        }
+    }
+
+    // One of the `starts` that `dst` can be reached from, if any
+    fn reaching(&self, starts: &[usize], dst: usize) -> Option<usize>
+    {
+        let mut origin: Vec<Option<usize>> = vec![None; self.nodes.len()];
+        let mut queue = VecDeque::new();
+        for &s in starts
+        {
+            if origin[s].is_none()
+            {
+                origin[s] = Some(s);
+                queue.push_back(s);
+            }
+        }
+        while let Some(u) = queue.pop_front()
+        {
+            for e in self.adjacent(u)
+            {
+                if origin[e.node].is_none()
+                {
+                    origin[e.node] = origin[u];
+                    queue.push_back(e.node);
+                }
+            }
+        }
+        origin[dst]
+    }
+
+    fn find_cycle(&self, parent: &[Option<usize>], start: usize) -> Vec<K>
+    {
+        // Walking back |V| parents from a node relaxed in the last round is
+        // guaranteed to end up on the cycle itself
+        let mut node = start;
+        for _ in 0..self.nodes.len()
+        {
+            node = parent[node].unwrap();
+        }
+
+        let mut cycle = vec![self.nodes[node].clone()];
+        let mut p = parent[node].unwrap();
+        while p != node
+        {
+            cycle.push(self.nodes[p].clone());
+            p = parent[p].unwrap();
+        }
+        cycle.push(self.nodes[node].clone());
+        cycle.reverse();
+        cycle
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn negative_weights()
+    {
+        let mut g = SignedInternetOfThings::new();
+        g.set_nodes(vec![1, 2, 3, 4]);
+        g.set_edges(1, vec![(4, 2), (1, 3)]);
+        g.set_edges(3, vec![(-3, 2)]);
+        g.set_edges(2, vec![(2, 4)]);
+        assert_eq!(g.bellman_ford(1, 4), Ok(Some((0, vec![1, 3, 2, 4]))));
+        assert_eq!(g.bellman_ford(4, 1), Ok(None));
+        assert_eq!(g.bellman_ford(1, 1), Ok(Some((0, vec![1]))));
+    }
+
+    #[test]
+    fn reachable_negative_cycle()
+    {
+        let mut g = SignedInternetOfThings::new();
+        g.set_nodes(vec![1, 2, 3, 4, 5]);
+        g.set_edges(1, vec![(1, 2)]);
+        g.set_edges(2, vec![(1, 3)]);
+        g.set_edges(3, vec![(1, 4), (1, 5)]);
+        g.set_edges(4, vec![(-3, 2)]);
+
+        match g.bellman_ford(1, 5)
+        {
+            Err(PathError::NegativeCycle(cycle)) =>
+            {
+                assert_eq!(cycle.first(), cycle.last());
+                let mut nodes = cycle[1..].to_vec();
+                nodes.sort();
+                assert_eq!(nodes, vec![2, 3, 4]);
+            }
+            other => panic!("expected a negative cycle, got {:?}", other)
+        }
+
+        // The cycle can't be reached from node 5
+        assert_eq!(g.bellman_ford(5, 1), Ok(None));
+
+        // Node 6 hangs off node 1, so the cycle can't reach it
+        g.add_node(6);
+        g.add_edge(1, 6, 7).unwrap();
+        assert_eq!(g.bellman_ford(1, 6), Ok(Some((7, vec![1, 6]))));
+    }
+
+    #[test]
+    fn signed_overflow()
+    {
+        let mut g = SignedInternetOfThings::new();
+        g.set_nodes(vec![1, 2, 3]);
+        g.set_edges(1, vec![(i64::MAX, 2)]);
+        g.set_edges(2, vec![(1, 3)]);
+        assert_eq!(g.bellman_ford(1, 3), Err(PathError::Overflow(3)));
+        assert_eq!(g.bellman_ford(1, 2), Ok(Some((i64::MAX, vec![1, 2]))));
+
+        // Overflowing elsewhere in the graph doesn't affect other targets
+        g.add_node(4);
+        g.add_edge(1, 4, -5).unwrap();
+        assert_eq!(g.bellman_ford(1, 4), Ok(Some((-5, vec![1, 4]))));
+
+        // A route that fits beats one that overflows
+        g.add_edge(1, 3, 2).unwrap();
+        assert_eq!(g.bellman_ford(1, 3), Ok(Some((2, vec![1, 3]))));
+
+        // Even when it is found after the overflowing one
+        let mut g = SignedInternetOfThings::new();
+        g.set_nodes(vec![1, 2, 3, 4]);
+        g.set_edges(1, vec![(i64::MAX, 2), (5, 4)]);
+        g.set_edges(2, vec![(1, 3)]);
+        g.set_edges(4, vec![(-10, 3)]);
+        assert_eq!(g.bellman_ford(1, 3), Ok(Some((-5, vec![1, 4, 3]))));
+    }
+
+    #[test]
+    fn signed_underflow()
+    {
+        let mut g = SignedInternetOfThings::new();
+        g.set_nodes(vec![1, 2, 3]);
+        g.set_edges(1, vec![(i64::MIN, 2), (5, 3)]);
+        g.set_edges(2, vec![(-1, 3)]);
+        // Cheaper than the route that fits, so that one isn't the answer
+        assert_eq!(g.bellman_ford(1, 3), Err(PathError::Underflow(3)));
+        assert_eq!(g.bellman_ford(1, 2), Ok(Some((i64::MIN, vec![1, 2]))));
+        assert_eq!(PathError::Underflow(3).to_string(),
+            "Path cost to node 3 underflows the weight type");
+    }
+}
@@ -0,0 +1,646 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::hash::Hash;
+
+use super::{Direction, Edge, Graph, GraphMode, Multiplicity, NonNegativeF64, TotalF64};
+
+// Snapshot layout, integers as LEB128 varints unless noted:
+//   "IOTG" | version (u16 LE) | mode flags (u8) | node count | nodes
+//   | per node: edge count, then target index and weight per edge
+//   | CRC-32 of everything before it (u32 LE)
+const MAGIC: &[u8; 4] = b"IOTG";
+const VERSION: u16 = 1;
+const UNDIRECTED: u8 = 1;
+const SIMPLE: u8 = 2;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum SnapshotError
+{
+    BadMagic,
+    UnsupportedVersion(u16),
+    ChecksumMismatch { stored: u32, computed: u32 },
+    Truncated,
+    // The checksum matched, but the contents don't make up a valid graph
+    Corrupt(String)
+}
+
+impl fmt::Display for SnapshotError
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        match self
+        {
+            SnapshotError::BadMagic => write!(f, "not a graph snapshot"),
+            SnapshotError::UnsupportedVersion(v) =>
+                write!(f, "unsupported snapshot version {} (expected {})", v, VERSION),
+            SnapshotError::ChecksumMismatch { stored, computed } =>
+                write!(f, "snapshot is corrupted: checksum {:08x} does not match contents ({:08x})",
+                    stored, computed),
+            SnapshotError::Truncated => write!(f, "snapshot is truncated"),
+            SnapshotError::Corrupt(reason) => write!(f, "invalid snapshot: {}", reason)
+        }
+    }
+}
+
+impl Error for SnapshotError {}
+
+// Node keys and edge weights that can be written into a snapshot
+pub trait SnapshotValue: Sized
+{
+    fn write(&self, out: &mut Vec<u8>);
+
+    fn read(input: &mut &[u8]) -> Result<Self, SnapshotError>;
+}
+
+macro_rules! unsigned_value
+{
+    ($($t:ty),*) =>
+    {
+        $(
+            impl SnapshotValue for $t
+            {
+                fn write(&self, out: &mut Vec<u8>)
+                {
+                    write_varint(out, *self as u128);
+                }
+
+                fn read(input: &mut &[u8]) -> Result<$t, SnapshotError>
+                {
+                    let value = read_varint(input)?;
+                    <$t>::try_from(value).map_err(|_| out_of_range(value))
+                }
+            }
+        )*
+    }
+}
+
+// Zigzag encoded so small negative numbers stay short
+macro_rules! signed_value
+{
+    ($($t:ty),*) =>
+    {
+        $(
+            impl SnapshotValue for $t
+            {
+                fn write(&self, out: &mut Vec<u8>)
+                {
+                    let v = *self as i128;
+                    write_varint(out, ((v << 1) ^ (v >> 127)) as u128);
+                }
+
+                fn read(input: &mut &[u8]) -> Result<$t, SnapshotError>
+                {
+                    let raw = read_varint(input)?;
+                    let value = (raw >> 1) as i128 ^ -((raw & 1) as i128);
+                    <$t>::try_from(value).map_err(|_| out_of_range(raw))
+                }
+            }
+        )*
+    }
+}
+
+unsigned_value!(u8, u16, u32, u64, u128, usize);
+signed_value!(i8, i16, i32, i64, i128, isize);
+
+impl SnapshotValue for String
+{
+    fn write(&self, out: &mut Vec<u8>)
+    {
+        write_varint(out, self.len() as u128);
+        out.extend_from_slice(self.as_bytes());
+    }
+
+    fn read(input: &mut &[u8]) -> Result<String, SnapshotError>
+    {
+        let len = usize::read(input)?;
+        let bytes = take(input, len)?;
+        String::from_utf8(bytes.to_vec())
+            .map_err(|_| SnapshotError::Corrupt("string is not valid UTF-8".to_string()))
+    }
+}
+
+impl SnapshotValue for TotalF64
+{
+    fn write(&self, out: &mut Vec<u8>)
+    {
+        out.extend_from_slice(&self.0.to_le_bytes());
+    }
+
+    fn read(input: &mut &[u8]) -> Result<TotalF64, SnapshotError>
+    {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(take(input, 8)?);
+        Ok(TotalF64(f64::from_le_bytes(bytes)))
+    }
+}
+
+impl SnapshotValue for NonNegativeF64
+{
+    fn write(&self, out: &mut Vec<u8>)
+    {
+        TotalF64(self.get()).write(out);
+    }
+
+    fn read(input: &mut &[u8]) -> Result<NonNegativeF64, SnapshotError>
+    {
+        let value = TotalF64::read(input)?.0;
+        NonNegativeF64::new(value)
+            .ok_or_else(|| SnapshotError::Corrupt(format!("weight {} is negative", value)))
+    }
+}
+
+impl<A: SnapshotValue, B: SnapshotValue> SnapshotValue for (A, B)
+{
+    fn write(&self, out: &mut Vec<u8>)
+    {
+        self.0.write(out);
+        self.1.write(out);
+    }
+
+    fn read(input: &mut &[u8]) -> Result<(A, B), SnapshotError>
+    {
+        Ok((A::read(input)?, B::read(input)?))
+    }
+}
+
+impl<K: Hash + Eq + Clone + SnapshotValue, W: Clone + PartialEq + SnapshotValue> Graph<K, W>
+{
+    pub fn to_snapshot(&self) -> Vec<u8>
+    {
+        let mut out = MAGIC.to_vec();
+        out.extend_from_slice(&VERSION.to_le_bytes());
+        let mut flags = 0;
+        if !self.mode.is_directed()
+        {
+            flags |= UNDIRECTED;
+        }
+        if self.mode.multiplicity == Multiplicity::Simple
+        {
+            flags |= SIMPLE;
+        }
+        out.push(flags);
+
+        write_varint(&mut out, self.nodes.len() as u128);
+        for node in self.nodes.iter()
+        {
+            node.write(&mut out);
+        }
+        for edges in self.adjacency_list.iter()
+        {
+            write_varint(&mut out, edges.len() as u128);
+            for e in edges
+            {
+                write_varint(&mut out, e.node as u128);
+                e.weight.write(&mut out);
+            }
+        }
+
+        let checksum = crc32(&out);
+        out.extend_from_slice(&checksum.to_le_bytes());
+        out
+    }
+
+    pub fn from_snapshot(bytes: &[u8]) -> Result<Graph<K, W>, SnapshotError>
+    {
+        if bytes.len() < MAGIC.len() || &bytes[..MAGIC.len()] != MAGIC
+        {
+            return Err(match MAGIC.starts_with(bytes)
+            {
+                true => SnapshotError::Truncated,
+                false => SnapshotError::BadMagic
+            });
+        }
+        if bytes.len() < MAGIC.len() + 2 + 1 + 4
+        {
+            return Err(SnapshotError::Truncated);
+        }
+        let version = u16::from_le_bytes([bytes[4], bytes[5]]);
+        if version != VERSION
+        {
+            return Err(SnapshotError::UnsupportedVersion(version));
+        }
+
+        let (body, trailer) = bytes.split_at(bytes.len() - 4);
+        let stored = u32::from_le_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]);
+        let computed = crc32(body);
+        if stored != computed
+        {
+            return Err(SnapshotError::ChecksumMismatch { stored, computed });
+        }
+
+        let mut input = &body[6..];
+        let flags = take(&mut input, 1)?[0];
+        if flags & !(UNDIRECTED | SIMPLE) != 0
+        {
+            return Err(SnapshotError::Corrupt(format!("unknown mode flags {:#04x}", flags)));
+        }
+        let direction = match flags & UNDIRECTED
+        {
+            0 => Direction::Directed,
+            _ => Direction::Undirected
+        };
+        let multiplicity = match flags & SIMPLE
+        {
+            0 => Multiplicity::Multi,
+            _ => Multiplicity::Simple
+        };
+
+        // Counts come from the file, so nothing is preallocated from them
+        let count = usize::read(&mut input)?;
+        let mut nodes = vec![];
+        for _ in 0..count
+        {
+            nodes.push(K::read(&mut input)?);
+        }
+        let mut adjacency_list = vec![];
+        for _ in 0..count
+        {
+            let mut edges = vec![];
+            for _ in 0..usize::read(&mut input)?
+            {
+                let node = usize::read(&mut input)?;
+                edges.push(Edge { weight: W::read(&mut input)?, node });
+            }
+            adjacency_list.push(edges);
+        }
+        if !input.is_empty()
+        {
+            let reason = format!("{} bytes after the last edge", input.len());
+            return Err(SnapshotError::Corrupt(reason));
+        }
+
+        Graph::from_parts(adjacency_list, nodes, GraphMode::new(direction, multiplicity))
+    }
+}
+
+impl<K: Hash + Eq + Clone, W: Clone + PartialEq> Graph<K, W>
+{
+    fn from_parts(adjacency_list: Vec<Vec<Edge<W>>>, nodes: Vec<K>, mode: GraphMode)
+        -> Result<Graph<K, W>, SnapshotError>
+    {
+        if adjacency_list.len() != nodes.len()
+        {
+            return Err(SnapshotError::Corrupt(format!("{} nodes but {} edge lists",
+                nodes.len(), adjacency_list.len())));
+        }
+        let target = adjacency_list.iter().flatten().map(|e| e.node).find(|n| *n >= nodes.len());
+        if let Some(n) = target
+        {
+            return Err(SnapshotError::Corrupt(format!("edge to node #{} of {}", n, nodes.len())));
+        }
+
+        let mut index = HashMap::with_capacity(nodes.len());
+        for (i, n) in nodes.iter().enumerate()
+        {
+            if let Some(first) = index.insert(n.clone(), i)
+            {
+                return Err(SnapshotError::Corrupt(format!("node #{} repeats node #{}", i, first)));
+            }
+        }
+        if mode.multiplicity == Multiplicity::Simple
+        {
+            check_simple(&adjacency_list)?;
+        }
+        if !mode.is_directed()
+        {
+            check_symmetric(&adjacency_list)?;
+        }
+        Ok(Graph { adjacency_list, nodes, index, mode })
+    }
+}
+
+// A simple graph has at most one edge from any node to another
+fn check_simple<W>(adjacency_list: &[Vec<Edge<W>>]) -> Result<(), SnapshotError>
+{
+    for (u, edges) in adjacency_list.iter().enumerate()
+    {
+        let mut targets: Vec<usize> = edges.iter().map(|e| e.node).collect();
+        targets.sort_unstable();
+        if let Some(pair) = targets.windows(2).find(|pair| pair[0] == pair[1])
+        {
+            return Err(SnapshotError::Corrupt(format!(
+                "simple graph has more than one edge from node #{} to #{}", u, pair[0])));
+        }
+    }
+    Ok(())
+}
+
+// Undirected edges are stored in both lists (self loops only once), so every
+// edge needs a reverse edge of the same weight
+fn check_symmetric<W: PartialEq>(adjacency_list: &[Vec<Edge<W>>]) -> Result<(), SnapshotError>
+{
+    let mut unmatched: HashMap<(usize, usize), Vec<&W>> = HashMap::new();
+    for (u, edges) in adjacency_list.iter().enumerate()
+    {
+        for e in edges.iter().filter(|e| e.node != u)
+        {
+            if u < e.node
+            {
+                unmatched.entry((u, e.node)).or_default().push(&e.weight);
+                continue;
+            }
+            // The lower node's list has been seen already
+            let weights = unmatched.entry((e.node, u)).or_default();
+            match weights.iter().position(|w| **w == e.weight)
+            {
+                Some(i) =>
+                {
+                    weights.swap_remove(i);
+                }
+                None => return Err(missing_reverse(u, e.node))
+            }
+        }
+    }
+    match unmatched.into_iter().find(|(_, weights)| !weights.is_empty())
+    {
+        Some(((u, v), _)) => Err(missing_reverse(u, v)),
+        None => Ok(())
+    }
+}
+
+fn missing_reverse(from: usize, to: usize) -> SnapshotError
+{
+    SnapshotError::Corrupt(format!("undirected edge from node #{} to #{} has no reverse",
+        from, to))
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+pub(crate) struct GraphData<K, W>
+{
+    adjacency_list: Vec<Vec<Edge<W>>>,
+    nodes: Vec<K>,
+    mode: GraphMode
+}
+
+#[cfg(feature = "serde")]
+impl<K: Hash + Eq + Clone, W: Clone + PartialEq> TryFrom<GraphData<K, W>> for Graph<K, W>
+{
+    type Error = SnapshotError;
+
+    fn try_from(data: GraphData<K, W>) -> Result<Graph<K, W>, SnapshotError>
+    {
+        Graph::from_parts(data.adjacency_list, data.nodes, data.mode)
+    }
+}
+
+fn out_of_range(value: u128) -> SnapshotError
+{
+    SnapshotError::Corrupt(format!("value {} out of range", value))
+}
+
+fn take<'a>(input: &mut &'a [u8], len: usize) -> Result<&'a [u8], SnapshotError>
+{
+    if input.len() < len
+    {
+        return Err(SnapshotError::Truncated);
+    }
+    let (bytes, rest) = input.split_at(len);
+    *input = rest;
+    Ok(bytes)
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u128)
+{
+    while value >= 0x80
+    {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn read_varint(input: &mut &[u8]) -> Result<u128, SnapshotError>
+{
+    let mut value = 0u128;
+    let mut shift = 0;
+    loop
+    {
+        let byte = take(input, 1)?[0];
+        if shift >= 128 || (shift == 126 && byte > 0x03)
+        {
+            return Err(SnapshotError::Corrupt("varint too long".to_string()));
+        }
+        value |= ((byte & 0x7f) as u128) << shift;
+        if byte & 0x80 == 0
+        {
+            return Ok(value);
+        }
+        shift += 7;
+    }
+}
+
+// CRC-32 (IEEE), bit by bit; snapshots are written rarely enough
+fn crc32(bytes: &[u8]) -> u32
+{
+    let mut crc = !0u32;
+    for byte in bytes
+    {
+        crc ^= *byte as u32;
+        for _ in 0..8
+        {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::test_support::{random_graph, Lcg};
+    use crate::{InternetOfThings, SignedInternetOfThings};
+
+    fn sample() -> InternetOfThings
+    {
+        let mut g = InternetOfThings::new();
+        g.set_nodes(vec![1, 300, 70000]);
+        g.set_edges(1, vec![(5, 300), (1, 70000)]);
+        g.set_edges(70000, vec![(u32::MAX, 1)]);
+        g
+    }
+
+    #[test]
+    fn crc_of_known_input()
+    {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+    }
+
+    #[test]
+    fn round_trip()
+    {
+        let g = sample();
+        let restored = InternetOfThings::from_snapshot(&g.to_snapshot()).unwrap();
+        assert_eq!(restored.to_edge_list_csv(), g.to_edge_list_csv());
+        assert_eq!(restored.shorted_path(1, 300), Some((5, vec![1, 300])));
+
+        let mut rng = Lcg(3);
+        let g = random_graph(&mut rng, 50, 4);
+        let restored = InternetOfThings::from_snapshot(&g.to_snapshot()).unwrap();
+        assert_eq!(restored.all_pairs_shortest_paths(), g.all_pairs_shortest_paths());
+    }
+
+    #[test]
+    fn round_trip_other_types()
+    {
+        let mode = GraphMode::new(Direction::Undirected, Multiplicity::Simple);
+        let weight = (3u16, NonNegativeF64::new(0.25).unwrap());
+        let mut g: Graph<String, (u16, NonNegativeF64)> = Graph::with_mode(mode);
+        g.set_nodes(vec!["gateway".to_string(), "sensør".to_string()]);
+        g.add_edge("gateway".to_string(), "sensør".to_string(), weight).unwrap();
+        let bytes = g.to_snapshot();
+        let restored = Graph::<String, (u16, NonNegativeF64)>::from_snapshot(&bytes).unwrap();
+        assert_eq!(restored.mode(), mode);
+        assert_eq!(restored.edges(), 1);
+        let path = restored.shorted_path("sensør".to_string(), "gateway".to_string());
+        assert_eq!(path.map(|(cost, _)| cost), Some(weight));
+
+        // The same bytes read as signed floats can't pass for non-negative ones
+        let mut floats: Graph<u8, TotalF64> = Graph::new();
+        floats.set_nodes(vec![0, 1]);
+        floats.add_edge(0, 1, TotalF64(-1.5)).unwrap();
+        let bytes = floats.to_snapshot();
+        assert_eq!(
+            Graph::<u8, TotalF64>::from_snapshot(&bytes).unwrap().bellman_ford(0, 1),
+            Ok(Some((TotalF64(-1.5), vec![0, 1])))
+        );
+        assert!(matches!(
+            Graph::<u8, NonNegativeF64>::from_snapshot(&bytes),
+            Err(SnapshotError::Corrupt(_))
+        ));
+
+        let mut signed = SignedInternetOfThings::new();
+        signed.set_nodes(vec![0, 1]);
+        signed.set_edges(0, vec![(i64::MIN, 1), (i64::MAX, 0)]);
+        let restored = SignedInternetOfThings::from_snapshot(&signed.to_snapshot()).unwrap();
+        assert_eq!(restored.to_edge_list_csv(), signed.to_edge_list_csv());
+    }
+
+    #[test]
+    fn corruption_is_rejected()
+    {
+        let bytes = sample().to_snapshot();
+        for i in 0..bytes.len()
+        {
+            let mut flipped = bytes.clone();
+            flipped[i] ^= 0x10;
+            assert!(InternetOfThings::from_snapshot(&flipped).is_err(), "byte {}", i);
+        }
+        for len in 0..bytes.len()
+        {
+            assert!(InternetOfThings::from_snapshot(&bytes[..len]).is_err(), "length {}", len);
+        }
+
+        let mut flipped = bytes.clone();
+        flipped[10] ^= 1;
+        let err = InternetOfThings::from_snapshot(&flipped).unwrap_err();
+        assert!(matches!(err, SnapshotError::ChecksumMismatch { .. }));
+        assert!(err.to_string().contains("corrupted"));
+
+        let err = InternetOfThings::from_snapshot(b"garbage!!!").err();
+        assert_eq!(err, Some(SnapshotError::BadMagic));
+        let mut future = bytes;
+        future[4] = 2;
+        let err = InternetOfThings::from_snapshot(&future).err();
+        assert_eq!(err, Some(SnapshotError::UnsupportedVersion(2)));
+    }
+
+    #[test]
+    fn well_formed_but_invalid_contents()
+    {
+        // Re-checksummed so only the structural checks can catch it
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&VERSION.to_le_bytes());
+        bytes.extend_from_slice(&[0, 1, 7, 1, 3, 1]);
+        let checksum = crc32(&bytes);
+        bytes.extend_from_slice(&checksum.to_le_bytes());
+        let err = InternetOfThings::from_snapshot(&bytes).unwrap_err();
+        assert_eq!(err, SnapshotError::Corrupt("edge to node #3 of 1".to_string()));
+    }
+
+    #[test]
+    fn duplicate_nodes_are_rejected()
+    {
+        let edges = vec![vec![], vec![]];
+        let err = InternetOfThings::from_parts(edges, vec![4, 4], GraphMode::default());
+        assert_eq!(err.err(), Some(SnapshotError::Corrupt("node #1 repeats node #0".to_string())));
+    }
+
+    #[test]
+    fn undirected_edges_need_a_reverse()
+    {
+        let mode = GraphMode::new(Direction::Undirected, Multiplicity::Multi);
+        let edge = |node, weight| Edge { node, weight };
+        let parts = |lists: Vec<Vec<Edge<u32>>>|
+        {
+            InternetOfThings::from_parts(lists, vec![1, 2, 3], mode).map(|g| g.edges())
+        };
+        let missing = |from, to|
+        {
+            Err(SnapshotError::Corrupt(format!(
+                "undirected edge from node #{} to #{} has no reverse",
+                from, to
+            )))
+        };
+
+        // Parallel edges and self loops, which are stored once
+        let lists =
+            vec![vec![edge(1, 5), edge(1, 5), edge(0, 2)], vec![edge(0, 5), edge(0, 5)], vec![]];
+        assert_eq!(parts(lists), Ok(3));
+
+        let lists = vec![vec![edge(1, 5)], vec![], vec![]];
+        assert_eq!(parts(lists), missing(0, 1));
+        let lists = vec![vec![], vec![], vec![edge(0, 5)]];
+        assert_eq!(parts(lists), missing(2, 0));
+        let lists = vec![vec![edge(1, 5)], vec![edge(0, 6)], vec![]];
+        assert_eq!(parts(lists), missing(1, 0));
+        let lists = vec![vec![edge(1, 5), edge(1, 5)], vec![edge(0, 5)], vec![]];
+        assert_eq!(parts(lists), missing(0, 1));
+
+        // The same lists are fine for a directed graph
+        let lists = vec![vec![edge(1, 5)], vec![], vec![]];
+        assert!(InternetOfThings::from_parts(lists, vec![1, 2, 3], GraphMode::default()).is_ok());
+    }
+
+    #[test]
+    fn simple_graphs_reject_parallel_edges()
+    {
+        let edge = |node, weight| Edge { node, weight };
+        let parts = |lists: Vec<Vec<Edge<u32>>>, multiplicity|
+        {
+            let mode = GraphMode::new(Direction::Directed, multiplicity);
+            InternetOfThings::from_parts(lists, vec![1, 2, 3], mode).map(|g| g.edges())
+        };
+
+        let lists = vec![vec![edge(1, 5), edge(2, 1), edge(1, 7)], vec![], vec![]];
+        assert_eq!(parts(lists.clone(), Multiplicity::Multi), Ok(3));
+        assert_eq!(parts(lists, Multiplicity::Simple), Err(SnapshotError::Corrupt(
+            "simple graph has more than one edge from node #0 to #1".to_string())));
+
+        // One edge each way is not a parallel edge
+        let lists = vec![vec![edge(1, 5)], vec![edge(0, 5)], vec![]];
+        assert_eq!(parts(lists, Multiplicity::Simple), Ok(2));
+
+        let mode = GraphMode::new(Direction::Undirected, Multiplicity::Simple);
+        let lists = vec![vec![edge(1, 5), edge(1, 5)], vec![edge(0, 5), edge(0, 5)], vec![]];
+        assert!(InternetOfThings::from_parts(lists, vec![1, 2, 3], mode).is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip()
+    {
+        let g = sample();
+        let json = serde_json::to_string(&g).unwrap();
+        let restored: InternetOfThings = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.to_edge_list_csv(), g.to_edge_list_csv());
+        assert!(restored.contains_node(70000));
+
+        let broken = json.replace("\"node\":2", "\"node\":9");
+        let err = serde_json::from_str::<InternetOfThings>(&broken).unwrap_err();
+        assert!(err.to_string().contains("edge to node #9 of 3"));
+    }
+}
@@ -0,0 +1,85 @@
+use std::hash::Hash;
+
+use super::traversal::{forward_to_view, GraphView};
+use super::union_find::UnionFind;
+
+forward_to_view!
+{
+    [K: Hash + Eq + Clone] (K, u32)
+    fn minimum_spanning_tree(&self) -> (u64, Vec<(K, K, u32)>);
+}
+
+impl<'a, K: Hash + Eq + Clone> GraphView<'a, K, u32>
+{
+    // Kruskal's algorithm with every edge treated as an undirected link.
+    // A disconnected graph yields a spanning forest, one tree per component.
+    // Returns the total weight and the chosen (from, to, weight) edges.
+    pub(crate) fn minimum_spanning_tree(&self) -> (u64, Vec<(K, K, u32)>)
+    {
+        let mut edges: Vec<(u32, usize, usize)> = (0..self.nodes.len())
+            .flat_map(|from| self.adjacent(from).iter().map(move |e| (e.weight, from, e.node)))
+            .filter(|(_, from, to)| from != to)
+            .collect();
+        edges.sort_unstable();
+
+        let mut sets = UnionFind::new(self.nodes.len());
+        let mut total = 0u64;
+        let mut tree = vec![];
+        for (weight, from, to) in edges
+        {
+            if sets.union(from, to)
+            {
+                total += weight as u64;
+                tree.push((self.nodes[from].clone(), self.nodes[to].clone(), weight));
+                if sets.sets() == 1
+                {
+                    break;
+                }
+            }
+        }
+        (total, tree)
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use crate::test_support::{random_graph, Lcg};
+    use crate::InternetOfThings;
+
+    #[test]
+    fn spanning_tree()
+    {
+        let mut g = InternetOfThings::new();
+        g.set_nodes(vec![1, 2, 3, 4]);
+        g.set_edges(1, vec![(1, 2), (4, 3), (3, 4)]);
+        g.set_edges(2, vec![(2, 3)]);
+        g.set_edges(4, vec![(5, 3), (1, 4)]);
+        let (total, edges) = g.minimum_spanning_tree();
+        assert_eq!(total, 6);
+        assert_eq!(edges, vec![(1, 2, 1), (2, 3, 2), (1, 4, 3)]);
+    }
+
+    #[test]
+    fn spanning_forest_for_disconnected_graph()
+    {
+        let mut g = InternetOfThings::new();
+        g.set_nodes(vec![1, 2, 3, 4, 5]);
+        g.set_edges(2, vec![(7, 1)]);
+        g.set_edges(3, vec![(2, 4), (9, 4)]);
+        let (total, edges) = g.minimum_spanning_tree();
+        assert_eq!(total, 9);
+        assert_eq!(edges.len(), 2);
+    }
+
+    #[test]
+    fn spanning_forest_covers_every_component()
+    {
+        let mut rng = Lcg(99);
+        let g = random_graph(&mut rng, 100, 2);
+        let (total, edges) = g.minimum_spanning_tree();
+        let components = g.weakly_connected_components().len();
+        assert_eq!(edges.len(), g.nodes() - components);
+        assert_eq!(total, edges.iter().map(|e| e.2 as u64).sum::<u64>());
+    }
+}
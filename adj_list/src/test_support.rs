@@ -0,0 +1,70 @@
+// Helpers shared by the unit tests and the benchmarks, which include this
+// file with `#[path]`. Most tests build random graphs with these and check a
+// result against a recomputation from scratch or a simpler algorithm.
+use crate::{Direction, GraphMode, InternetOfThings, Multiplicity};
+
+// Small LCG so the random graphs are reproducible without extra crates
+pub struct Lcg(pub u64);
+
+impl Lcg
+{
+    pub fn next(&mut self) -> u64
+    {
+        self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        self.0 >> 33
+    }
+
+    pub fn below(&mut self, n: u64) -> u64
+    {
+        self.next() % n
+    }
+
+    // One of the keys handed out by `keys(nodes)`
+    pub fn key(&mut self, nodes: u64) -> u64
+    {
+        self.below(nodes) * 10
+    }
+
+    // `count` random (from, to) queries over `keys(nodes)`
+    pub fn pairs(&mut self, nodes: u64, count: usize) -> Vec<(u64, u64)>
+    {
+        (0..count).map(|_| (self.key(nodes), self.key(nodes))).collect()
+    }
+}
+
+// Keys are 0, 10, 20, ... so they never line up with the node indices
+pub fn keys(nodes: u64) -> Vec<u64>
+{
+    (0..nodes).map(|n| n * 10).collect()
+}
+
+// Directed graph over `keys(nodes)` with up to `edges_per_node` edges each
+pub fn random_graph(rng: &mut Lcg, nodes: u64, edges_per_node: u64) -> InternetOfThings
+{
+    let mut g = InternetOfThings::new();
+    g.set_nodes(keys(nodes));
+    for n in 0..nodes
+    {
+        let edges = (0..rng.below(edges_per_node + 1))
+            .map(|_| (rng.below(100) as u32 + 1, rng.key(nodes)))
+            .collect();
+        g.set_edges(n * 10, edges);
+    }
+    g
+}
+
+// Undirected multigraph over `keys(nodes)` with `edges` edges, weighing
+// 1 to `max_weight` each
+pub fn random_undirected(rng: &mut Lcg, nodes: u64, edges: u64, max_weight: u64)
+    -> InternetOfThings
+{
+    let mut g = InternetOfThings::with_mode(GraphMode::new(Direction::Undirected,
+        Multiplicity::Multi));
+    g.set_nodes(keys(nodes));
+    for _ in 0..edges
+    {
+        let weight = 1 + rng.below(max_weight) as u32;
+        g.add_edge(rng.key(nodes), rng.key(nodes), weight).unwrap();
+    }
+    g
+}
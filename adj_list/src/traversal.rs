@@ -0,0 +1,254 @@
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+
+use super::{build_path, Edge, GraphMode};
+
+// Visits every node reachable from the start node exactly once, nearest first,
+// yielding each node with the number of hops it takes to get there.
+pub struct BreadthFirst<'a, K, W>
+{
+    nodes: &'a [K],
+    edges: Adjacency<'a, W>,
+    queue: VecDeque<(usize, usize)>,
+    visited: Vec<bool>
+}
+
+// Where the outgoing edges of a node live: a list per node, or one flat array
+// split up by offsets for frozen graphs
+pub(crate) enum Adjacency<'a, W>
+{
+    Lists(&'a [Vec<Edge<W>>]),
+    Compressed { offsets: &'a [usize], edges: &'a [Edge<W>] }
+}
+
+impl<'a, W> Adjacency<'a, W>
+{
+    fn of(&self, node: usize) -> &'a [Edge<W>]
+    {
+        match *self
+        {
+            Adjacency::Lists(lists) => &lists[node],
+            Adjacency::Compressed { offsets, edges } => &edges[offsets[node]..offsets[node + 1]]
+        }
+    }
+}
+
+impl<'a, W> Clone for Adjacency<'a, W>
+{
+    fn clone(&self) -> Self
+    {
+        *self
+    }
+}
+
+impl<'a, W> Copy for Adjacency<'a, W> {}
+
+// Read-only view of a graph, whichever way its edges are stored. The
+// algorithms are written once against it and both `Graph` and `FrozenGraph`
+// hand their queries on to it.
+pub(crate) struct GraphView<'a, K, W>
+{
+    pub(crate) nodes: &'a [K],
+    pub(crate) index: &'a HashMap<K, usize>,
+    pub(crate) edges: Adjacency<'a, W>,
+    pub(crate) mode: GraphMode
+}
+
+impl<'a, K, W> Clone for GraphView<'a, K, W>
+{
+    fn clone(&self) -> Self
+    {
+        *self
+    }
+}
+
+impl<'a, K, W> Copy for GraphView<'a, K, W> {}
+
+impl<'a, K: Hash + Eq + Clone, W> GraphView<'a, K, W>
+{
+    pub(crate) fn adjacent(&self, node: usize) -> &'a [Edge<W>]
+    {
+        self.edges.of(node)
+    }
+
+    // Every edge once, also for undirected graphs where both directions are stored
+    pub(crate) fn edge_list(&self) -> impl Iterator<Item = (usize, &'a Edge<W>)>
+    {
+        let (directed, edges) = (self.mode.is_directed(), self.edges);
+        (0..self.nodes.len()).flat_map(move |from|
+        {
+            edges.of(from).iter()
+                .filter(move |e| directed || e.node >= from)
+                .map(move |e| (from, e))
+        })
+    }
+
+    pub(crate) fn get_node_index(&self, node: &K) -> Option<usize>
+    {
+        self.index.get(node).copied()
+    }
+
+    pub(crate) fn build_path(&self, parent: &[Option<usize>], dst: usize) -> Vec<K>
+    {
+        build_path(self.nodes, parent, dst)
+    }
+
+    pub(crate) fn by_key<T>(&self, values: Vec<T>) -> HashMap<K, T>
+    {
+        self.nodes.iter().cloned().zip(values).collect()
+    }
+
+    pub(crate) fn breadth_first(&self, from: K) -> Option<BreadthFirst<'a, K, W>>
+    {
+        let (nodes, edges) = (self.nodes, self.edges);
+        self.get_node_index(&from).map(|i| BreadthFirst::start(nodes, edges, i))
+    }
+
+    pub(crate) fn connected_with_distance(&self, from: K, degree: usize)
+        -> Option<HashMap<K, usize>>
+    {
+        self.breadth_first(from).map(|bfs|
+        {
+            bfs.take_while(|(_, hops)| *hops <= degree).collect()
+        })
+    }
+}
+
+impl<'a, K, W> BreadthFirst<'a, K, W>
+{
+    fn start(nodes: &'a [K], edges: Adjacency<'a, W>, from: usize) -> BreadthFirst<'a, K, W>
+    {
+        let mut visited = vec![false; nodes.len()];
+        visited[from] = true;
+        BreadthFirst
+        {
+            nodes,
+            edges,
+            queue: vec![(from, 0)].into_iter().collect(),
+            visited
+        }
+    }
+}
+
+impl<'a, K: Clone, W> Iterator for BreadthFirst<'a, K, W>
+{
+    type Item = (K, usize);
+
+    fn next(&mut self) -> Option<(K, usize)>
+    {
+        let (node, hops) = self.queue.pop_front()?;
+        for e in self.edges.of(node)
+        {
+            if !self.visited[e.node]
+            {
+                self.visited[e.node] = true;
+                self.queue.push_back((e.node, hops + 1));
+            }
+        }
+        Some((self.nodes[node].clone(), hops))
+    }
+}
+
+// Gives `Graph` and `FrozenGraph` a public method for each `GraphView`
+// method listed, with the same signature, that runs it on the graph's view
+macro_rules! forward_to_view
+{
+    (
+        [$($generics:tt)*] ($($types:ty),*)
+        $(fn $name:ident(&self $(, $arg:ident: $ty:ty)*) -> $ret:ty;)*
+    ) =>
+    {
+        impl<$($generics)*> $crate::Graph<$($types),*>
+        {
+            $(
+                pub fn $name(&self $(, $arg: $ty)*) -> $ret
+                {
+                    self.view().$name($($arg),*)
+                }
+            )*
+        }
+
+        impl<$($generics)*> $crate::FrozenGraph<$($types),*>
+        {
+            $(
+                pub fn $name(&self $(, $arg: $ty)*) -> $ret
+                {
+                    self.view().$name($($arg),*)
+                }
+            )*
+        }
+    }
+}
+
+pub(crate) use forward_to_view;
+
+forward_to_view!
+{
+    [K: Hash + Eq + Clone, W: Clone] (K, W)
+    fn breadth_first(&self, from: K) -> Option<BreadthFirst<'_, K, W>>;
+    fn connected_with_distance(&self, from: K, degree: usize) -> Option<HashMap<K, usize>>;
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::{InternetOfThings, KeyType};
+    use std::collections::HashSet;
+
+    fn ring(size: u64) -> InternetOfThings
+    {
+        let mut g = InternetOfThings::new();
+        g.set_nodes((0..size).collect());
+        for n in 0..size
+        {
+            g.set_edges(n, vec![(1, (n + 1) % size), (1, (n + size - 1) % size)]);
+        }
+        g
+    }
+
+    #[test]
+    fn hop_distances()
+    {
+        let g = ring(10);
+        let hops = g.connected_with_distance(0, 2).unwrap();
+        let expected: HashMap<KeyType, usize> =
+            vec![(0, 0), (1, 1), (9, 1), (2, 2), (8, 2)].into_iter().collect();
+        assert_eq!(hops, expected);
+        assert!(g.connected_with_distance(99, 2).is_none());
+    }
+
+    #[test]
+    fn breadth_first_visits_each_node_once()
+    {
+        let g = ring(7);
+        let visited: Vec<(KeyType, usize)> = g.breadth_first(3).unwrap().collect();
+        assert_eq!(visited.len(), 7);
+        assert_eq!(visited[0], (3, 0));
+        assert!(visited.windows(2).all(|w| w[0].1 <= w[1].1));
+        let unique: HashSet<KeyType> = visited.iter().map(|v| v.0).collect();
+        assert_eq!(unique.len(), 7);
+    }
+
+    #[test]
+    fn connected_on_dense_cyclic_graph()
+    {
+        // Every node links to every other; the old recursion was exponential here
+        let mut g = InternetOfThings::new();
+        g.set_nodes((0..200).collect());
+        for n in 0..200
+        {
+            g.set_edges(n, (0..200).filter(|m| *m != n).map(|m| (1, m)).collect());
+        }
+        let connected = g.connected(0, 50).unwrap();
+        assert_eq!(connected.len(), 200);
+
+        let mut chain = InternetOfThings::new();
+        chain.set_nodes(vec![1, 2, 3]);
+        chain.set_edges(1, vec![(1, 2)]);
+        chain.set_edges(2, vec![(1, 3)]);
+        let expected: HashSet<KeyType> = vec![2].into_iter().collect();
+        assert_eq!(chain.connected(1, 1), Some(expected));
+        assert_eq!(chain.connected(3, 5), Some(HashSet::new()));
+    }
+}
@@ -0,0 +1,102 @@
+// Disjoint sets over the indices 0..n with union by rank and path compression
+pub struct UnionFind
+{
+    parent: Vec<usize>,
+    rank: Vec<u8>,
+    sets: usize
+}
+
+impl UnionFind
+{
+    pub fn new(size: usize) -> UnionFind
+    {
+        UnionFind
+        {
+            parent: (0..size).collect(),
+            rank: vec![0; size],
+            sets: size
+        }
+    }
+
+    pub fn find(&mut self, element: usize) -> usize
+    {
+        let mut root = element;
+        while self.parent[root] != root
+        {
+            root = self.parent[root];
+        }
+
+        let mut current = element;
+        while self.parent[current] != root
+        {
+            let next = self.parent[current];
+            self.parent[current] = root;
+            current = next;
+        }
+        root
+    }
+
+    // Returns false when both elements were already in the same set
+    pub fn union(&mut self, a: usize, b: usize) -> bool
+    {
+        let (a, b) = (self.find(a), self.find(b));
+        if a == b
+        {
+            return false;
+        }
+
+        if self.rank[a] < self.rank[b]
+        {
+            self.parent[a] = b;
+        } else if self.rank[a] > self.rank[b]
+        {
+            self.parent[b] = a;
+        } else {
+            self.parent[b] = a;
+            self.rank[a] += 1;
+        }
+        self.sets -= 1;
+        true
+    }
+
+    pub fn connected(&mut self, a: usize, b: usize) -> bool
+    {
+        self.find(a) == self.find(b)
+    }
+
+    pub fn sets(&self) -> usize
+    {
+        self.sets
+    }
+
+    pub fn len(&self) -> usize
+    {
+        self.parent.len()
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        self.parent.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn union_and_find()
+    {
+        let mut sets = UnionFind::new(6);
+        assert_eq!(sets.sets(), 6);
+        assert!(sets.union(0, 1));
+        assert!(sets.union(2, 3));
+        assert!(sets.union(1, 3));
+        assert!(!sets.union(0, 2));
+        assert!(sets.connected(0, 3));
+        assert!(!sets.connected(0, 4));
+        assert_eq!(sets.sets(), 3);
+        assert_eq!(sets.len(), 6);
+    }
+}
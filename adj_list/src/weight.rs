@@ -0,0 +1,245 @@
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt::{self, Debug};
+
+// What the path algorithms need from an edge weight: a starting point,
+// a way to add two weights up and an order to pick the cheapest by.
+// Adding returns None when the sum can't be represented.
+pub trait Weight: Copy + Ord + Debug
+{
+    fn zero() -> Self;
+
+    fn checked_add(self, other: Self) -> Option<Self>;
+}
+
+macro_rules! integer_weight
+{
+    ($($t:ty),*) =>
+    {
+        $(
+            impl Weight for $t
+            {
+                fn zero() -> Self
+                {
+                    0
+                }
+
+                fn checked_add(self, other: Self) -> Option<Self>
+                {
+                    <$t>::checked_add(self, other)
+                }
+            }
+        )*
+    }
+}
+
+integer_weight!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize);
+
+// Weights that can never be below zero. Dijkstra and everything built on it
+// (A*, Yen, centrality, cached trees) need this to be correct; graphs with
+// signed weights have to use `bellman_ford`.
+pub trait NonNegative: Weight {}
+
+impl NonNegative for u8 {}
+impl NonNegative for u16 {}
+impl NonNegative for u32 {}
+impl NonNegative for u64 {}
+impl NonNegative for u128 {}
+impl NonNegative for usize {}
+impl<A: NonNegative, B: NonNegative> NonNegative for (A, B) {}
+
+// Non-negative weights that read as a single number, for metrics that add up
+// or divide distances such as closeness
+pub trait Numeric: NonNegative
+{
+    fn to_f64(self) -> f64;
+}
+
+macro_rules! numeric_weight
+{
+    ($($t:ty),*) =>
+    {
+        $(
+            impl Numeric for $t
+            {
+                fn to_f64(self) -> f64
+                {
+                    self as f64
+                }
+            }
+        )*
+    }
+}
+
+numeric_weight!(u8, u16, u32, u64, u128, usize);
+
+// Composite weights compare lexicographically, e.g. (latency, hops)
+impl<A: Weight, B: Weight> Weight for (A, B)
+{
+    fn zero() -> Self
+    {
+        (A::zero(), B::zero())
+    }
+
+    fn checked_add(self, other: Self) -> Option<Self>
+    {
+        Some((self.0.checked_add(other.0)?, self.1.checked_add(other.1)?))
+    }
+}
+
+// f64 with a total order, so it can be used as a weight. Sums that are no
+// longer finite count as overflow.
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TotalF64(pub f64);
+
+impl PartialEq for TotalF64
+{
+    fn eq(&self, other: &TotalF64) -> bool
+    {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for TotalF64 {}
+
+impl Ord for TotalF64
+{
+    fn cmp(&self, other: &TotalF64) -> Ordering
+    {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+impl PartialOrd for TotalF64
+{
+    fn partial_cmp(&self, other: &TotalF64) -> Option<Ordering>
+    {
+        Some(self.cmp(other))
+    }
+}
+
+impl Weight for TotalF64
+{
+    fn zero() -> Self
+    {
+        TotalF64(0.0)
+    }
+
+    fn checked_add(self, other: Self) -> Option<Self>
+    {
+        let sum = self.0 + other.0;
+        if sum.is_finite()
+        {
+            Some(TotalF64(sum))
+        } else {
+            None
+        }
+    }
+}
+
+// f64 that is known to be neither negative nor NaN, so unlike `TotalF64` it
+// can be used with the Dijkstra-based searches
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "f64", into = "f64"))]
+pub struct NonNegativeF64(f64);
+
+impl NonNegativeF64
+{
+    pub fn new(value: f64) -> Option<NonNegativeF64>
+    {
+        if value >= 0.0
+        {
+            // abs turns -0.0 into 0.0, which would otherwise order below it
+            Some(NonNegativeF64(value.abs()))
+        } else {
+            None
+        }
+    }
+
+    pub fn get(self) -> f64
+    {
+        self.0
+    }
+}
+
+// Returned when a value can't be a `NonNegativeF64`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NegativeWeight(pub f64);
+
+impl fmt::Display for NegativeWeight
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        write!(f, "Weight {} is negative or not a number", self.0)
+    }
+}
+
+impl Error for NegativeWeight {}
+
+impl TryFrom<f64> for NonNegativeF64
+{
+    type Error = NegativeWeight;
+
+    fn try_from(value: f64) -> Result<NonNegativeF64, NegativeWeight>
+    {
+        NonNegativeF64::new(value).ok_or(NegativeWeight(value))
+    }
+}
+
+impl From<NonNegativeF64> for f64
+{
+    fn from(weight: NonNegativeF64) -> f64
+    {
+        weight.0
+    }
+}
+
+impl Eq for NonNegativeF64 {}
+
+impl Ord for NonNegativeF64
+{
+    fn cmp(&self, other: &NonNegativeF64) -> Ordering
+    {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+impl PartialOrd for NonNegativeF64
+{
+    fn partial_cmp(&self, other: &NonNegativeF64) -> Option<Ordering>
+    {
+        Some(self.cmp(other))
+    }
+}
+
+impl Weight for NonNegativeF64
+{
+    fn zero() -> Self
+    {
+        NonNegativeF64(0.0)
+    }
+
+    fn checked_add(self, other: Self) -> Option<Self>
+    {
+        let sum = self.0 + other.0;
+        if sum.is_finite()
+        {
+            Some(NonNegativeF64(sum))
+        } else {
+            None
+        }
+    }
+}
+
+impl NonNegative for NonNegativeF64 {}
+
+impl Numeric for NonNegativeF64
+{
+    fn to_f64(self) -> f64
+    {
+        self.0
+    }
+}
@@ -1,4 +1,4 @@
-use std::mem;
+use std::iter::FromIterator;
 
 type Tree = Option<Box<Node>>;
 
@@ -39,7 +39,7 @@ impl BinarySearchTree {
 
     pub fn add(&mut self, device: IoTDevice) {
         self.length += 1;
-        let root = mem::replace(&mut self.root, None);
+        let root = self.root.take();
         self.root = self.add_rec(root, device)
     }
 
@@ -77,15 +77,152 @@ impl BinarySearchTree {
         }
     }
 
-    pub fn walk(&self, callback: impl Fn(&IoTDevice) -> ()) {
-        self.walk_in_order(&self.root, &callback)
+    pub fn len(&self) -> usize {
+        self.length as usize
     }
 
-    fn walk_in_order(&self, node: &Tree, callback: &impl Fn(&IoTDevice) -> ()) {
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    pub fn walk(&self, callback: impl FnMut(&IoTDevice)) {
+        self.iter().for_each(callback)
+    }
+
+    // In-order like `walk`, which puts larger ids first in this tree
+    pub fn iter(&self) -> Iter<'_> {
+        let mut iter = Iter {
+            front: vec![],
+            back: vec![],
+            remaining: self.len(),
+        };
+        iter.push_left(&self.root);
+        iter.push_right(&self.root);
+        iter
+    }
+
+    fn into_in_order(node: Tree, devices: &mut Vec<IoTDevice>) {
         if let Some(n) = node {
-            self.walk_in_order(&n.left, callback);
-            callback(&n.dev);
-            self.walk_in_order(&n.right, callback)
+            BinarySearchTree::into_in_order(n.left, devices);
+            devices.push(n.dev);
+            BinarySearchTree::into_in_order(n.right, devices)
+        }
+    }
+}
+
+pub struct Iter<'a> {
+    front: Vec<&'a Node>,
+    back: Vec<&'a Node>,
+    // Both stacks may hold the same nodes, this keeps the ends from crossing
+    remaining: usize,
+}
+
+impl<'a> Iter<'a> {
+    fn push_left(&mut self, mut node: &'a Tree) {
+        while let Some(n) = node {
+            self.front.push(n);
+            node = &n.left;
+        }
+    }
+
+    fn push_right(&mut self, mut node: &'a Tree) {
+        while let Some(n) = node {
+            self.back.push(n);
+            node = &n.right;
+        }
+    }
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = &'a IoTDevice;
+
+    fn next(&mut self) -> Option<&'a IoTDevice> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let n = self.front.pop()?;
+        self.remaining -= 1;
+        self.push_left(&n.right);
+        Some(&n.dev)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a> DoubleEndedIterator for Iter<'a> {
+    fn next_back(&mut self) -> Option<&'a IoTDevice> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let n = self.back.pop()?;
+        self.remaining -= 1;
+        self.push_right(&n.left);
+        Some(&n.dev)
+    }
+}
+
+impl<'a> ExactSizeIterator for Iter<'a> {}
+
+pub struct IntoIter {
+    devices: std::vec::IntoIter<IoTDevice>,
+}
+
+impl Iterator for IntoIter {
+    type Item = IoTDevice;
+
+    fn next(&mut self) -> Option<IoTDevice> {
+        self.devices.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.devices.size_hint()
+    }
+}
+
+impl DoubleEndedIterator for IntoIter {
+    fn next_back(&mut self) -> Option<IoTDevice> {
+        self.devices.next_back()
+    }
+}
+
+impl ExactSizeIterator for IntoIter {}
+
+impl IntoIterator for BinarySearchTree {
+    type Item = IoTDevice;
+    type IntoIter = IntoIter;
+
+    fn into_iter(self) -> IntoIter {
+        let mut devices = Vec::with_capacity(self.len());
+        BinarySearchTree::into_in_order(self.root, &mut devices);
+        IntoIter {
+            devices: devices.into_iter(),
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a BinarySearchTree {
+    type Item = &'a IoTDevice;
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Iter<'a> {
+        self.iter()
+    }
+}
+
+impl FromIterator<IoTDevice> for BinarySearchTree {
+    fn from_iter<I: IntoIterator<Item = IoTDevice>>(devices: I) -> BinarySearchTree {
+        let mut tree = BinarySearchTree::new_empty();
+        tree.extend(devices);
+        tree
+    }
+}
+
+impl Extend<IoTDevice> for BinarySearchTree {
+    fn extend<I: IntoIterator<Item = IoTDevice>>(&mut self, devices: I) {
+        for device in devices {
+            self.add(device);
         }
     }
 }
@@ -101,4 +238,48 @@ mod tests {
         let my_devices: RefCell<Vec<IoTDevice>> = RefCell::new(vec![]);
         tree.walk(|n| my_devices.borrow_mut().push(n.clone()));
     }
+
+    fn device(id: u64) -> IoTDevice {
+        IoTDevice {
+            numerical_id: id,
+            address: format!("10.0.0.{}", id),
+        }
+    }
+
+    fn ids<'a>(devices: impl Iterator<Item = &'a IoTDevice>) -> Vec<u64> {
+        devices.map(|d| d.numerical_id).collect()
+    }
+
+    #[test]
+    fn iterators() {
+        let tree: BinarySearchTree = [5, 2, 8, 1, 9, 5, 3].iter().map(|id| device(*id)).collect();
+        assert_eq!(tree.length, 7);
+        assert_eq!(tree.len(), 7);
+        assert_eq!(ids(tree.iter()), vec![9, 8, 5, 5, 3, 2, 1]);
+        assert_eq!(ids(tree.iter().rev()), vec![1, 2, 3, 5, 5, 8, 9]);
+        assert_eq!(ids((&tree).into_iter().skip(5)), vec![2, 1]);
+
+        let mut iter = tree.iter();
+        assert_eq!(iter.len(), 7);
+        assert_eq!(iter.next().map(|d| d.numerical_id), Some(9));
+        assert_eq!(iter.next_back().map(|d| d.numerical_id), Some(1));
+        assert_eq!(iter.len(), 5);
+        assert_eq!(ids(iter.by_ref().rev().take(2)), vec![2, 3]);
+        assert_eq!(ids(iter.by_ref()), vec![8, 5, 5]);
+        assert_eq!(iter.next_back().map(|d| d.numerical_id), None);
+
+        let owned: Vec<u64> = tree.into_iter().rev().map(|d| d.numerical_id).collect();
+        assert_eq!(owned, vec![1, 2, 3, 5, 5, 8, 9]);
+    }
+
+    #[test]
+    fn extend() {
+        let mut tree = BinarySearchTree::new_empty();
+        assert_eq!(tree.iter().next().map(|d| d.numerical_id), None);
+        tree.extend((0..4).map(device));
+        tree.extend(vec![device(10)]);
+        assert_eq!(tree.iter().len(), 5);
+        assert_eq!(ids(tree.iter()), vec![10, 3, 2, 1, 0]);
+        assert_eq!(tree.find(2).map(|d| d.address), Some("10.0.0.2".to_string()));
+    }
 }
\ No newline at end of file
@@ -1,12 +1,19 @@
 #![allow(dead_code)]
 
 use std::cmp;
+use std::error::Error;
+use std::fmt;
+use std::iter::FromIterator;
 use std::mem;
+use std::ops::{Bound, Deref, RangeBounds};
 
 type Tree = Box<Node>;
 type KeyType = u64;
 type Data = (Option<IoTDevice>, Option<Tree>);
 
+// Order used by `Default` and `FromIterator`
+pub const DEFAULT_ORDER: usize = 8;
+
 #[derive(Clone, PartialEq, Debug)]
 enum NodeType
 {
@@ -21,7 +28,7 @@ enum Direction
     Right(usize)
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct IoTDevice
 {
     numerical_id: u64,
@@ -44,6 +51,198 @@ impl IoTDevice
             path
         }
     }
+
+    pub fn numerical_id(&self) -> u64
+    {
+        self.numerical_id
+    }
+
+    pub fn address(&self) -> &str
+    {
+        &self.address
+    }
+
+    pub fn path(&self) -> &str
+    {
+        &self.path
+    }
+}
+
+// Returned by `insert_unique` together with the device that was turned away
+#[derive(Debug)]
+pub struct DuplicateDevice(pub IoTDevice);
+
+impl fmt::Display for DuplicateDevice
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        write!(f, "A device with id {} already exists", self.0.numerical_id)
+    }
+}
+
+impl Error for DuplicateDevice {}
+
+// Returned by `from_sorted` for input that isn't strictly ascending by id,
+// or for an order no B-tree can have
+#[derive(Debug, PartialEq)]
+pub enum FromSortedError
+{
+    Duplicate(KeyType),
+    OutOfOrder { previous: KeyType, next: KeyType },
+    InvalidOrder(usize)
+}
+
+impl fmt::Display for FromSortedError
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        match self
+        {
+            FromSortedError::Duplicate(id) => write!(f, "Device id {} appears twice", id),
+            FromSortedError::OutOfOrder { previous, next } =>
+                write!(f, "Device id {} comes after {}, input must be sorted", next, previous),
+            FromSortedError::InvalidOrder(order) =>
+                write!(f, "Order {} is too small, a B-tree needs at least 3", order)
+        }
+    }
+}
+
+impl Error for FromSortedError {}
+
+// Mutable access to a stored device. Only the address and path can change,
+// the id is what the device is sorted by.
+pub struct DeviceMut<'a>(&'a mut IoTDevice);
+
+impl<'a> DeviceMut<'a>
+{
+    // Both setters return the value they replaced
+    pub fn set_address(&mut self, address: String) -> String
+    {
+        mem::replace(&mut self.0.address, address)
+    }
+
+    pub fn set_path(&mut self, path: String) -> String
+    {
+        mem::replace(&mut self.0.path, path)
+    }
+}
+
+impl<'a> Deref for DeviceMut<'a>
+{
+    type Target = IoTDevice;
+
+    fn deref(&self) -> &IoTDevice
+    {
+        self.0
+    }
+}
+
+// A view into a single id of a `DeviceDatabase`, like `BTreeMap::entry`
+pub enum Entry<'a>
+{
+    Occupied(OccupiedEntry<'a>),
+    Vacant(VacantEntry<'a>)
+}
+
+pub struct OccupiedEntry<'a>
+{
+    database: &'a mut DeviceDatabase,
+    id: KeyType
+}
+
+pub struct VacantEntry<'a>
+{
+    database: &'a mut DeviceDatabase,
+    id: KeyType
+}
+
+impl<'a> Entry<'a>
+{
+    pub fn id(&self) -> KeyType
+    {
+        match self
+        {
+            Entry::Occupied(e) => e.id,
+            Entry::Vacant(e) => e.id
+        }
+    }
+
+    pub fn or_insert(self, address: String, path: String) -> DeviceMut<'a>
+    {
+        self.or_insert_with(|| (address, path))
+    }
+
+    // `default` gives the address and path of a new device
+    pub fn or_insert_with(self, default: impl FnOnce() -> (String, String)) -> DeviceMut<'a>
+    {
+        match self
+        {
+            Entry::Occupied(e) => e.into_mut(),
+            Entry::Vacant(e) =>
+            {
+                let (address, path) = default();
+                e.insert(address, path)
+            }
+        }
+    }
+
+    pub fn and_modify(mut self, f: impl FnOnce(&mut DeviceMut)) -> Entry<'a>
+    {
+        if let Entry::Occupied(ref mut e) = self
+        {
+            f(&mut e.get_mut());
+        }
+        self
+    }
+}
+
+impl<'a> OccupiedEntry<'a>
+{
+    pub fn id(&self) -> KeyType
+    {
+        self.id
+    }
+
+    pub fn get(&self) -> &IoTDevice
+    {
+        self.database.get(self.id).unwrap()
+    }
+
+    pub fn get_mut(&mut self) -> DeviceMut<'_>
+    {
+        DeviceMut(self.database.get_mut(self.id).unwrap())
+    }
+
+    pub fn into_mut(self) -> DeviceMut<'a>
+    {
+        DeviceMut(self.database.get_mut(self.id).unwrap())
+    }
+
+    // Returns the device as it was before
+    pub fn insert(&mut self, address: String, path: String) -> IoTDevice
+    {
+        let device = IoTDevice::new(self.id, address, path);
+        mem::replace(self.database.get_mut(self.id).unwrap(), device)
+    }
+
+    pub fn remove(self) -> IoTDevice
+    {
+        self.database.remove(self.id).unwrap()
+    }
+}
+
+impl<'a> VacantEntry<'a>
+{
+    pub fn id(&self) -> KeyType
+    {
+        self.id
+    }
+
+    pub fn insert(self, address: String, path: String) -> DeviceMut<'a>
+    {
+        self.database.add(IoTDevice::new(self.id, address, path));
+        DeviceMut(self.database.get_mut(self.id).unwrap())
+    }
 }
 
 #[derive(Clone)]
@@ -102,7 +301,7 @@ impl Node
         {
             Direction::Left =>
             {
-                let tree = mem::replace(&mut self.left_child, None);
+                let tree = self.left_child.take();
                 Some((id, (None, tree)))
             },
             Direction::Right(index) => 
@@ -135,15 +334,12 @@ impl Node
     pub fn get_device(&self, key: KeyType) -> Option<&IoTDevice>
     {
         let mut result = None;
-        for d in self.devices.iter()
+        for device in self.devices.iter().flatten()
         {
-            if let Some(device) = d
+            if device.numerical_id == key
             {
-                if device.numerical_id == key
-                {
-                    result = Some(device);
-                    break;
-                }
+                result = Some(device);
+                break;
             }
         }
         result
@@ -179,204 +375,1477 @@ impl Node
         (dev.unwrap(), sibiling)
     }
 
-    fn new(node_type: NodeType) -> Tree {
-        Box::new(Node {
-            left_child: None,
-            devices: vec![],
-            children: vec![],
-            node_type
-        })
+    // Appends the separator and everything in `right` to this node
+    fn merge(&mut self, separator: Option<IoTDevice>, right: Node)
+    {
+        self.devices.push(separator);
+        self.children.push(right.left_child);
+        self.devices.extend(right.devices);
+        self.children.extend(right.children);
     }
 
-}
-
-pub struct DeviceDatabase
-{
-    root: Option<Tree>,
-    order: usize,
-    pub length: u64
-}
-
-impl DeviceDatabase
-{
-    pub fn new_empty(order: usize) -> DeviceDatabase
+    // Children are numbered left to right: 0 is the left child, i + 1 the
+    // child to the right of device i
+    fn take_child(&mut self, index: usize) -> Tree
     {
-        DeviceDatabase
+        match index
         {
-            root: None,
-            order,
-            length: 0
-        }
+            0 => self.left_child.take(),
+            i => self.children[i - 1].take()
+        }.unwrap()
     }
 
-    pub fn is_a_valid_btree(&self) -> bool
+    fn put_child(&mut self, index: usize, tree: Tree)
     {
-        if let Some(tree) = self.root.as_ref()
+        match index
         {
-            let total = self.validate(tree, 0);
-            total.0 && total.1 == total.2
-        } else {
-            false // there is no tree
+            0 => self.left_child = Some(tree),
+            i => self.children[i - 1] = Some(tree)
         }
     }
 
-    pub fn add(&mut self, device: IoTDevice)
+    fn child_index(&self, key: KeyType) -> usize
     {
-        let node = if self.root.is_some()
+        match self.find_closest_index(key)
         {
-            mem::replace(&mut self.root, None).unwrap()
-        } else {
-            Node::new_leaf()
-        };
-
-        let (root, _) = self.add_r(node, device, true);
-        self.root = Some(root);
+            Direction::Left => 0,
+            Direction::Right(i) => i + 1
+        }
     }
 
-    pub fn find(&self, id: KeyType) -> Option<IoTDevice>
-    {
-        match self.root.as_ref()
-        {
-            Some(tree) => self.find_r(tree, id),
-            _ => None
-        }
+    fn new(node_type: NodeType) -> Tree {
+        Box::new(Node {
+            left_child: None,
+            devices: vec![],
+            children: vec![],
+            node_type
+        })
     }
 
-    pub fn walk(&self, callback: impl Fn(&IoTDevice) ->())
+}
+
+// Walks the devices in id order in either direction. Remembers the path
+// from the root, so stepping never starts over at the top. Past either end
+// the cursor sits on a "ghost" position from which it wraps around.
+pub struct Cursor<'a>
+{
+    root: Option<&'a Node>,
+    // Every frame but the last holds the index of the child it descended
+    // into, the last one the index of the current device
+    stack: Vec<(&'a Node, usize)>
+}
+
+impl<'a> Cursor<'a>
+{
+    pub fn current(&self) -> Option<&'a IoTDevice>
     {
-        if let Some(ref root) = self.root
-        {
-            self.walk_in_order(root, &callback);
-        }
+        let (node, i) = self.stack.last()?;
+        node.devices[*i].as_ref()
     }
 
-    fn validate(&self, node: &Tree, level: usize)
-        -> (bool, usize, usize)
+    pub fn move_next(&mut self)
     {
-        match node.node_type
+        let (node, i) = match self.stack.last_mut()
         {
-            NodeType::Leaf => (node.len() <= self.order, level, level),
-            NodeType::Regular =>
+            Some(top) => (top.0, &mut top.1),
+            None =>
             {
-                // Root node only requires two children,
-                // every other node at least half the order
-                let min_children = if level > 0 {
-                    self.order / 2usize } else { 2 };
-                let key_rules = node.len() <= self.order &&
-                    node.len() >= min_children;
+                if let Some(root) = self.root
+                {
+                    self.descend_leftmost(root);
+                }
+                return;
+            }
+        };
 
-                let mut total = (key_rules, usize::max_value(), level);
-                for n in node.children.iter().chain(vec![&node.left_child])
+        if node.node_type == NodeType::Regular
+        {
+            *i += 1;
+            let child = Cursor::child(node, *i);
+            self.descend_leftmost(child);
+        } else if *i + 1 < node.devices.len() {
+            *i += 1;
+        } else {
+            // Up to the first ancestor with a device right of where we came from
+            self.stack.pop();
+            while let Some((node, child)) = self.stack.last()
+            {
+                if *child < node.devices.len()
                 {
-                    if let Some(ref tree) = n
-                    {
-                        let stats = self.validate(tree, level + 1);
-                        total = 
-                        (
-                            total.0 && stats.0,
-                            cmp::min(stats.1, total.1),
-                            cmp::max(stats.2, total.2)
-                        );
-                    }
+                    return;
                 }
-                total
+                self.stack.pop();
             }
         }
     }
 
-    fn add_r(
-        &mut self,
-        node: Tree,
-        device: IoTDevice,
-        is_root: bool
-    ) -> (Tree, Option<Data>)
+    pub fn move_prev(&mut self)
     {
-        let mut node = node;
-        let id = device.numerical_id;
-
-        match node.node_type
+        let (node, i) = match self.stack.last_mut()
         {
-            NodeType::Leaf =>
+            Some(top) => (top.0, &mut top.1),
+            None =>
             {
-                if node.add_key(id, (Some(device), None))
+                if let Some(root) = self.root
                 {
-                    self.length += 1;
+                    self.descend_rightmost(root);
                 }
+                return;
             }
-            NodeType::Regular =>
+        };
+
+        if node.node_type == NodeType::Regular
+        {
+            let child = Cursor::child(node, *i);
+            self.descend_rightmost(child);
+        } else if *i > 0 {
+            *i -= 1;
+        } else {
+            self.stack.pop();
+            while let Some((_, child)) = self.stack.last_mut()
             {
-                let (key, (dev, tree)) = node.remove_key(id).unwrap();
-                let new = self.add_r(tree.unwrap(), device, false);
-                if dev.is_none()
-                {
-                    node.add_left_child(Some(new.0));
-                } else {
-                    node.add_key(key, (dev, Some(new.0)));
-                }
-                if let Some(split_result) = new.1
+                if *child > 0
                 {
-                    let new_id = &split_result.0.clone().unwrap();
-                    node.add_key(new_id.numerical_id, split_result);
+                    *child -= 1;
+                    return;
                 }
+                self.stack.pop();
             }
         }
-        if node.len() > self.order
-        {
-            let (new_parent, sibiling) = node.split();
+    }
 
-            // Check if the root node is "full" and add a new level
-            if is_root
+    // Moves to the first device with an id of at least `id`, or to the
+    // ghost position when there is none
+    pub fn seek(&mut self, id: KeyType)
+    {
+        self.stack.clear();
+        let mut node = match self.root
+        {
+            Some(root) => root,
+            None => return
+        };
+        loop
+        {
+            let i = node.devices.iter()
+                .position(|d| d.as_ref().is_some_and(|d| d.numerical_id >= id))
+                .unwrap_or(node.devices.len());
+            let exact = node.devices.get(i)
+                .is_some_and(|d| d.as_ref().is_some_and(|d| d.numerical_id == id));
+            if exact || (node.node_type == NodeType::Leaf && i < node.devices.len())
             {
-                let mut parent = Node::new_regular();
-                // Add the former root to the left
-                parent.add_left_child(Some(node));
-                // Add the new right part as well
-                parent.add_key(new_parent.numerical_id, (Some(new_parent), Some(sibiling)));
-                (parent, None)
-            } else {
-                (node, Some((Some(new_parent), Some(sibiling))))
+                self.stack.push((node, i));
+                return;
             }
-        } else {
-            (node, None)
+            if node.node_type == NodeType::Leaf
+            {
+                // Everything here is smaller, continue after the last one
+                self.stack.push((node, i - 1));
+                self.move_next();
+                return;
+            }
+            self.stack.push((node, i));
+            node = Cursor::child(node, i);
         }
     }
 
-    fn find_r(&self, node: &Tree, id: KeyType) -> Option<IoTDevice>
+    fn child(node: &'a Node, index: usize) -> &'a Node
     {
-        match node.get_device(id)
+        match index
         {
-            Some(device) => Some(device.clone()),
-            None if node.node_type != NodeType::Leaf =>
-            {
-                if let Some(tree) = node.get_child(id)
-                {
-                    self.find_r(tree, id)
-                } else {
-                    None
-                }
-            }
-            _ => None,
-        }
+            0 => node.left_child.as_deref(),
+            i => node.children[i - 1].as_deref()
+        }.unwrap()
     }
 
-    fn walk_in_order(&self, node: &Tree, callback: &impl Fn(&IoTDevice) ->())
+    fn descend_leftmost(&mut self, mut node: &'a Node)
     {
-        if let Some(ref left) = node.left_child
+        while node.node_type == NodeType::Regular
         {
-            self.walk_in_order(left, callback);
+            self.stack.push((node, 0));
+            node = Cursor::child(node, 0);
         }
+        self.stack.push((node, 0));
+    }
 
-        for i in 0..node.devices.len()
+    fn descend_rightmost(&mut self, mut node: &'a Node)
+    {
+        while node.node_type == NodeType::Regular
+        {
+            self.stack.push((node, node.devices.len()));
+            node = Cursor::child(node, node.devices.len());
+        }
+        self.stack.push((node, node.devices.len() - 1));
+    }
+}
+
+// Devices in an id range, see `DeviceDatabase::range`
+pub struct Range<'a>
+{
+    front: Cursor<'a>,
+    back: Cursor<'a>,
+    done: bool
+}
+
+impl<'a> Range<'a>
+{
+    fn step(&mut self, forward: bool) -> Option<&'a IoTDevice>
+    {
+        if self.done
+        {
+            return None;
+        }
+        let (front, back) = match (self.front.current(), self.back.current())
+        {
+            (Some(front), Some(back)) if front.numerical_id <= back.numerical_id => (front, back),
+            _ =>
+            {
+                self.done = true;
+                return None;
+            }
+        };
+        if front.numerical_id == back.numerical_id
+        {
+            self.done = true;
+        }
+        if forward
+        {
+            self.front.move_next();
+            Some(front)
+        } else {
+            self.back.move_prev();
+            Some(back)
+        }
+    }
+}
+
+impl<'a> Iterator for Range<'a>
+{
+    type Item = &'a IoTDevice;
+
+    fn next(&mut self) -> Option<&'a IoTDevice>
+    {
+        self.step(true)
+    }
+}
+
+impl<'a> DoubleEndedIterator for Range<'a>
+{
+    fn next_back(&mut self) -> Option<&'a IoTDevice>
+    {
+        self.step(false)
+    }
+}
+
+// All devices in id order, see `DeviceDatabase::iter`
+pub struct Iter<'a>
+{
+    range: Range<'a>,
+    remaining: usize
+}
+
+impl<'a> Iterator for Iter<'a>
+{
+    type Item = &'a IoTDevice;
+
+    fn next(&mut self) -> Option<&'a IoTDevice>
+    {
+        let device = self.range.next()?;
+        self.remaining -= 1;
+        Some(device)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>)
+    {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a> DoubleEndedIterator for Iter<'a>
+{
+    fn next_back(&mut self) -> Option<&'a IoTDevice>
+    {
+        let device = self.range.next_back()?;
+        self.remaining -= 1;
+        Some(device)
+    }
+}
+
+impl<'a> ExactSizeIterator for Iter<'a> {}
+
+pub struct IntoIter
+{
+    devices: std::vec::IntoIter<IoTDevice>
+}
+
+impl Iterator for IntoIter
+{
+    type Item = IoTDevice;
+
+    fn next(&mut self) -> Option<IoTDevice>
+    {
+        self.devices.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>)
+    {
+        self.devices.size_hint()
+    }
+}
+
+impl DoubleEndedIterator for IntoIter
+{
+    fn next_back(&mut self) -> Option<IoTDevice>
+    {
+        self.devices.next_back()
+    }
+}
+
+impl ExactSizeIterator for IntoIter {}
+
+pub struct DeviceDatabase
+{
+    root: Option<Tree>,
+    order: usize,
+    pub length: u64
+}
+
+impl DeviceDatabase
+{
+    pub fn new_empty(order: usize) -> DeviceDatabase
+    {
+        DeviceDatabase
+        {
+            root: None,
+            order,
+            length: 0
+        }
+    }
+
+    // Builds the tree bottom-up with every node as full as the order allows
+    pub fn from_sorted(order: usize, devices: impl IntoIterator<Item = IoTDevice>)
+        -> Result<DeviceDatabase, FromSortedError>
+    {
+        DeviceDatabase::from_sorted_with_fill(order, 1.0, devices)
+    }
+
+    // `fill` is the share of each node's capacity to use, leaving room for
+    // later additions. It is kept within what a valid B-tree permits.
+    pub fn from_sorted_with_fill(
+        order: usize,
+        fill: f64,
+        devices: impl IntoIterator<Item = IoTDevice>
+    ) -> Result<DeviceDatabase, FromSortedError>
+    {
+        if order < 3
+        {
+            return Err(FromSortedError::InvalidOrder(order));
+        }
+        let mut db = DeviceDatabase::new_empty(order);
+
+        let mut items: Vec<IoTDevice> = vec![];
+        for device in devices
+        {
+            if let Some(previous) = items.last()
+            {
+                let (previous, next) = (previous.numerical_id, device.numerical_id);
+                if next == previous
+                {
+                    return Err(FromSortedError::Duplicate(next));
+                } else if next < previous
+                {
+                    return Err(FromSortedError::OutOfOrder { previous, next });
+                }
+            }
+            items.push(device);
+        }
+        if items.is_empty()
+        {
+            return Ok(db);
+        }
+        db.length = items.len() as u64;
+
+        let (least, most) = (db.min_devices(), order - 1);
+        let target = ((fill * most as f64).round() as usize).clamp(least, most);
+
+        // Each level turns its items into nodes and hands the separators
+        // between those nodes up as the items of the level above
+        let mut below: Vec<Tree> = vec![];
+        while items.len() > most
+        {
+            // k nodes keep all items but the k - 1 separators between them
+            let n = items.len() + 1;
+            let nodes = n.div_ceil(target + 1).clamp(n.div_ceil(most + 1), n / (least + 1));
+            let (per_node, extra) = ((n - nodes) / nodes, (n - nodes) % nodes);
+
+            let mut items_left = items.into_iter();
+            let mut children = below.into_iter();
+            let mut separators = Vec::with_capacity(nodes - 1);
+            let mut level = Vec::with_capacity(nodes);
+            for i in 0..nodes
+            {
+                let count = per_node + usize::from(i < extra);
+                level.push(DeviceDatabase::packed_node(
+                    items_left.by_ref().take(count).collect(),
+                    &mut children
+                ));
+                separators.extend(items_left.next());
+            }
+            items = separators;
+            below = level;
+        }
+        db.root = Some(DeviceDatabase::packed_node(items, &mut below.into_iter()));
+        Ok(db)
+    }
+
+    pub fn len(&self) -> usize
+    {
+        self.length as usize
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        self.length == 0
+    }
+
+    pub fn is_a_valid_btree(&self) -> bool
+    {
+        if let Some(tree) = self.root.as_ref()
+        {
+            let total = self.validate(tree, 0);
+            total.0 && total.1 == total.2
+        } else {
+            false // there is no tree
+        }
+    }
+
+    // Replaces and returns a device that has the same id, if there is one
+    pub fn add(&mut self, device: IoTDevice) -> Option<IoTDevice>
+    {
+        if let Some(existing) = self.get_mut(device.numerical_id)
+        {
+            return Some(mem::replace(existing, device));
+        }
+
+        let node = if self.root.is_some()
+        {
+            self.root.take().unwrap()
+        } else {
+            Node::new_leaf()
+        };
+
+        let (root, _) = self.add_r(node, device, true);
+        self.root = Some(root);
+        None
+    }
+
+    // Like `add`, but refuses to replace an existing device
+    pub fn insert_unique(&mut self, device: IoTDevice) -> Result<(), DuplicateDevice>
+    {
+        if self.get(device.numerical_id).is_some()
+        {
+            return Err(DuplicateDevice(device));
+        }
+        self.add(device);
+        Ok(())
+    }
+
+    pub fn entry(&mut self, id: KeyType) -> Entry<'_>
+    {
+        if self.get(id).is_some()
+        {
+            Entry::Occupied(OccupiedEntry { database: self, id })
+        } else {
+            Entry::Vacant(VacantEntry { database: self, id })
+        }
+    }
+
+    pub fn find(&self, id: KeyType) -> Option<IoTDevice>
+    {
+        match self.root.as_ref()
+        {
+            Some(tree) => self.find_r(tree, id),
+            _ => None
+        }
+    }
+
+    pub fn first(&self) -> Option<&IoTDevice>
+    {
+        self.cursor_front().current()
+    }
+
+    pub fn last(&self) -> Option<&IoTDevice>
+    {
+        self.cursor_back().current()
+    }
+
+    // The device with the largest id not above `id`
+    pub fn floor(&self, id: KeyType) -> Option<&IoTDevice>
+    {
+        let mut cursor = self.cursor_at(id);
+        if cursor.current().is_none_or(|d| d.numerical_id > id)
+        {
+            cursor.move_prev();
+        }
+        cursor.current()
+    }
+
+    // The device with the smallest id not below `id`
+    pub fn ceiling(&self, id: KeyType) -> Option<&IoTDevice>
+    {
+        self.cursor_at(id).current()
+    }
+
+    pub fn range(&self, range: impl RangeBounds<KeyType>) -> Range<'_>
+    {
+        let mut front = self.cursor_front();
+        match range.start_bound()
+        {
+            Bound::Included(start) => front.seek(*start),
+            Bound::Excluded(start) =>
+            {
+                front.seek(*start);
+                if front.current().is_some_and(|d| d.numerical_id == *start)
+                {
+                    front.move_next();
+                }
+            }
+            Bound::Unbounded => {}
+        }
+
+        let mut back = self.cursor_back();
+        match range.end_bound()
+        {
+            Bound::Included(end) =>
+            {
+                back.seek(*end);
+                if back.current().is_none_or(|d| d.numerical_id > *end)
+                {
+                    back.move_prev();
+                }
+            }
+            Bound::Excluded(end) =>
+            {
+                back.seek(*end);
+                back.move_prev();
+            }
+            Bound::Unbounded => {}
+        }
+        Range { front, back, done: false }
+    }
+
+    pub fn iter(&self) -> Iter<'_>
+    {
+        Iter { range: self.range(..), remaining: self.len() }
+    }
+
+    pub fn cursor_front(&self) -> Cursor<'_>
+    {
+        let mut cursor = Cursor { root: self.root.as_deref(), stack: vec![] };
+        cursor.move_next();
+        cursor
+    }
+
+    pub fn cursor_back(&self) -> Cursor<'_>
+    {
+        let mut cursor = Cursor { root: self.root.as_deref(), stack: vec![] };
+        cursor.move_prev();
+        cursor
+    }
+
+    pub fn cursor_at(&self, id: KeyType) -> Cursor<'_>
+    {
+        let mut cursor = Cursor { root: self.root.as_deref(), stack: vec![] };
+        cursor.seek(id);
+        cursor
+    }
+
+    // Borrowing counterpart of `find`
+    pub fn get(&self, id: KeyType) -> Option<&IoTDevice>
+    {
+        let mut node = self.root.as_ref()?;
+        loop
+        {
+            if let Some(device) = node.get_device(id)
+            {
+                return Some(device);
+            }
+            if node.node_type == NodeType::Leaf
+            {
+                return None;
+            }
+            node = match node.child_index(id)
+            {
+                0 => node.left_child.as_ref(),
+                i => node.children[i - 1].as_ref()
+            }?;
+        }
+    }
+
+    fn get_mut(&mut self, id: KeyType) -> Option<&mut IoTDevice>
+    {
+        let mut node = self.root.as_mut()?;
+        loop
+        {
+            let position = node.devices.iter()
+                .position(|d| d.as_ref().map(|d| d.numerical_id) == Some(id));
+            if let Some(i) = position
+            {
+                return node.devices[i].as_mut();
+            }
+            if node.node_type == NodeType::Leaf
+            {
+                return None;
+            }
+            node = match node.child_index(id)
+            {
+                0 => node.left_child.as_mut(),
+                i => node.children[i - 1].as_mut()
+            }?;
+        }
+    }
+
+    pub fn remove(&mut self, id: KeyType) -> Option<IoTDevice>
+    {
+        let mut root = self.root.take()?;
+        let removed = self.remove_r(&mut root, id);
+
+        // An emptied root hands over to its only child, or leaves no tree at all
+        self.root = if root.devices.is_empty()
+        {
+            root.left_child.take()
+        } else {
+            Some(root)
+        };
+        if removed.is_some()
+        {
+            self.length -= 1;
+        }
+        removed
+    }
+
+    pub fn walk(&self, callback: impl FnMut(&IoTDevice))
+    {
+        self.iter().for_each(callback);
+    }
+
+    fn validate(&self, node: &Tree, level: usize)
+        -> (bool, usize, usize)
+    {
+        match node.node_type
+        {
+            NodeType::Leaf => (node.len() <= self.order, level, level),
+            NodeType::Regular =>
+            {
+                // Root node only requires two children,
+                // every other node at least half the order
+                let min_children = if level > 0 {
+                    self.order / 2usize } else { 2 };
+                let key_rules = node.len() <= self.order &&
+                    node.len() >= min_children;
+
+                let mut total = (key_rules, usize::MAX, level);
+                for tree in node.children.iter().chain(vec![&node.left_child]).flatten()
+                {
+                    let stats = self.validate(tree, level + 1);
+                    total = 
+                    (
+                        total.0 && stats.0,
+                        cmp::min(stats.1, total.1),
+                        cmp::max(stats.2, total.2)
+                    );
+                }
+                total
+            }
+        }
+    }
+
+    fn add_r(
+        &mut self,
+        node: Tree,
+        device: IoTDevice,
+        is_root: bool
+    ) -> (Tree, Option<Data>)
+    {
+        let mut node = node;
+        let id = device.numerical_id;
+
+        match node.node_type
+        {
+            NodeType::Leaf =>
+            {
+                if node.add_key(id, (Some(device), None))
+                {
+                    self.length += 1;
+                }
+            }
+            NodeType::Regular =>
+            {
+                let (key, (dev, tree)) = node.remove_key(id).unwrap();
+                let new = self.add_r(tree.unwrap(), device, false);
+                if dev.is_none()
+                {
+                    node.add_left_child(Some(new.0));
+                } else {
+                    node.add_key(key, (dev, Some(new.0)));
+                }
+                if let Some(split_result) = new.1
+                {
+                    let new_id = &split_result.0.clone().unwrap();
+                    node.add_key(new_id.numerical_id, split_result);
+                }
+            }
+        }
+        if node.len() > self.order
+        {
+            let (new_parent, sibiling) = node.split();
+
+            // Check if the root node is "full" and add a new level
+            if is_root
+            {
+                let mut parent = Node::new_regular();
+                // Add the former root to the left
+                parent.add_left_child(Some(node));
+                // Add the new right part as well
+                parent.add_key(new_parent.numerical_id, (Some(new_parent), Some(sibiling)));
+                (parent, None)
+            } else {
+                (node, Some((Some(new_parent), Some(sibiling))))
+            }
+        } else {
+            (node, None)
+        }
+    }
+
+    fn remove_r(&self, node: &mut Tree, id: KeyType) -> Option<IoTDevice>
+    {
+        let position = node.devices.iter()
+            .position(|d| d.as_ref().map(|d| d.numerical_id) == Some(id));
+
+        match (position, &node.node_type)
+        {
+            (Some(i), NodeType::Leaf) =>
+            {
+                node.children.remove(i);
+                node.devices.remove(i)
+            }
+            (Some(i), NodeType::Regular) =>
+            {
+                // Replace the device with its predecessor, the largest one
+                // in the subtree to its left
+                let mut child = node.take_child(i);
+                let predecessor = self.remove_max(&mut child);
+                node.put_child(i, child);
+                let removed = node.devices[i].replace(predecessor);
+                self.rebalance(node, i);
+                removed
+            }
+            (None, NodeType::Leaf) => None,
+            (None, NodeType::Regular) =>
+            {
+                let index = node.child_index(id);
+                let mut child = node.take_child(index);
+                let removed = self.remove_r(&mut child, id);
+                node.put_child(index, child);
+                if removed.is_some()
+                {
+                    self.rebalance(node, index);
+                }
+                removed
+            }
+        }
+    }
+
+    fn remove_max(&self, node: &mut Tree) -> IoTDevice
+    {
+        match node.node_type
+        {
+            NodeType::Leaf =>
+            {
+                node.children.pop();
+                node.devices.pop().unwrap().unwrap()
+            }
+            NodeType::Regular =>
+            {
+                let index = node.devices.len();
+                let mut child = node.take_child(index);
+                let max = self.remove_max(&mut child);
+                node.put_child(index, child);
+                self.rebalance(node, index);
+                max
+            }
+        }
+    }
+
+    // Fewest devices a node other than the root may hold, matching the
+    // minimum number of children `validate` expects
+    fn min_devices(&self) -> usize
+    {
+        cmp::max(self.order / 2, 2) - 1
+    }
+
+    // Tops up the child at `index` after a removal, first by borrowing a
+    // device from a sibling through the parent, otherwise by merging it
+    // with a sibling
+    fn rebalance(&self, node: &mut Tree, index: usize)
+    {
+        let min = self.min_devices();
+        let mut child = node.take_child(index);
+        if child.devices.len() >= min
+        {
+            node.put_child(index, child);
+            return;
+        }
+
+        if index > 0
+        {
+            let mut left = node.take_child(index - 1);
+            if left.devices.len() > min
+            {
+                // Rotate right: the separator moves down, the left sibling's
+                // largest device moves up
+                let largest = left.devices.pop().unwrap();
+                let separator = mem::replace(&mut node.devices[index - 1], largest);
+                child.devices.insert(0, separator);
+                child.children.insert(0, child.left_child.take());
+                child.left_child = left.children.pop().unwrap();
+                node.put_child(index - 1, left);
+                node.put_child(index, child);
+            } else {
+                let separator = node.devices.remove(index - 1);
+                node.children.remove(index - 1);
+                left.merge(separator, *child);
+                node.put_child(index - 1, left);
+            }
+        } else if index < node.devices.len() {
+            let mut right = node.take_child(index + 1);
+            if right.devices.len() > min
+            {
+                // Rotate left: the separator moves down, the right sibling's
+                // smallest device moves up
+                let smallest = right.devices.remove(0);
+                let separator = mem::replace(&mut node.devices[index], smallest);
+                child.devices.push(separator);
+                child.children.push(right.left_child.take());
+                right.left_child = right.children.remove(0);
+                node.put_child(index, child);
+                node.put_child(index + 1, right);
+            } else {
+                let separator = node.devices.remove(index);
+                node.children.remove(index);
+                child.merge(separator, *right);
+                node.put_child(index, child);
+            }
+        } else {
+            // Only the root can be left without siblings
+            node.put_child(index, child);
+        }
+    }
+
+    fn find_r(&self, node: &Tree, id: KeyType) -> Option<IoTDevice>
+    {
+        match node.get_device(id)
+        {
+            Some(device) => Some(device.clone()),
+            None if node.node_type != NodeType::Leaf =>
+            {
+                if let Some(tree) = node.get_child(id)
+                {
+                    self.find_r(tree, id)
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        }
+    }
+
+    // A leaf when there are no children to take, otherwise a regular node
+    // with one more child than devices
+    fn packed_node(devices: Vec<IoTDevice>, children: &mut impl Iterator<Item = Tree>) -> Tree
+    {
+        let mut node = Node::new_leaf();
+        node.left_child = children.next();
+        if node.left_child.is_some()
+        {
+            node.node_type = NodeType::Regular;
+        }
+        for device in devices
+        {
+            node.devices.push(Some(device));
+            node.children.push(children.next());
+        }
+        node
+    }
+
+    fn into_sorted(node: Node, devices: &mut Vec<IoTDevice>)
+    {
+        if let Some(left) = node.left_child
+        {
+            DeviceDatabase::into_sorted(*left, devices);
+        }
+
+        for (device, child) in node.devices.into_iter().zip(node.children)
+        {
+            devices.extend(device);
+            if let Some(c) = child
+            {
+                DeviceDatabase::into_sorted(*c, devices);
+            }
+        }
+    }
+}
+
+impl IntoIterator for DeviceDatabase
+{
+    type Item = IoTDevice;
+    type IntoIter = IntoIter;
+
+    fn into_iter(self) -> IntoIter
+    {
+        let mut devices = Vec::with_capacity(self.len());
+        if let Some(root) = self.root
+        {
+            DeviceDatabase::into_sorted(*root, &mut devices);
+        }
+        IntoIter { devices: devices.into_iter() }
+    }
+}
+
+impl<'a> IntoIterator for &'a DeviceDatabase
+{
+    type Item = &'a IoTDevice;
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Iter<'a>
+    {
+        self.iter()
+    }
+}
+
+impl Default for DeviceDatabase
+{
+    fn default() -> DeviceDatabase
+    {
+        DeviceDatabase::new_empty(DEFAULT_ORDER)
+    }
+}
+
+// Adds the devices one at a time, so the input doesn't need to be sorted;
+// `from_sorted` is faster when it is
+impl FromIterator<IoTDevice> for DeviceDatabase
+{
+    fn from_iter<I: IntoIterator<Item = IoTDevice>>(devices: I) -> DeviceDatabase
+    {
+        let mut db = DeviceDatabase::default();
+        db.extend(devices);
+        db
+    }
+}
+
+impl Extend<IoTDevice> for DeviceDatabase
+{
+    fn extend<I: IntoIterator<Item = IoTDevice>>(&mut self, devices: I)
+    {
+        for device in devices
+        {
+            self.add(device);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use std::collections::BTreeMap;
+
+    // Small deterministic generator so the property tests need no dependencies
+    struct Lcg(u64);
+
+    impl Lcg
+    {
+        fn below(&mut self, n: u64) -> u64
+        {
+            self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (self.0 >> 33) % n
+        }
+    }
+
+    fn device(id: u64) -> IoTDevice
+    {
+        IoTDevice::new(id, format!("10.0.0.{}", id), format!("/devices/{}", id))
+    }
+
+    fn ids(db: &DeviceDatabase) -> Vec<u64>
+    {
+        let mut seen = vec![];
+        db.walk(|d| seen.push(d.numerical_id));
+        seen
+    }
+
+    fn assert_matches(db: &DeviceDatabase, model: &BTreeMap<u64, String>)
+    {
+        assert_eq!(ids(db), model.keys().copied().collect::<Vec<_>>());
+        assert_eq!(db.len(), model.len());
+        assert_eq!(db.is_a_valid_btree(), !model.is_empty());
+    }
+
+    #[test]
+    fn remove_from_leaf_root()
+    {
+        let mut db = DeviceDatabase::new_empty(4);
+        db.add(device(1));
+        db.add(device(2));
+        assert_eq!(db.remove(3).map(|d| d.numerical_id), None);
+        assert_eq!(db.remove(1).map(|d| d.address), Some("10.0.0.1".to_string()));
+        assert_eq!(ids(&db), vec![2]);
+        assert!(db.remove(2).is_some());
+        assert!(db.remove(2).is_none());
+        assert_eq!(db.len(), 0);
+        assert!(db.find(2).is_none());
+        db.add(device(5));
+        assert_eq!(ids(&db), vec![5]);
+    }
+
+    #[test]
+    fn remove_shrinks_the_tree()
+    {
+        let mut db = DeviceDatabase::new_empty(3);
+        for id in 0..100
+        {
+            db.add(device(id));
+        }
+        for id in (0..100).rev()
+        {
+            assert_eq!(db.remove(id).map(|d| d.numerical_id), Some(id));
+            assert!(db.find(id).is_none());
+            if id > 0
+            {
+                assert!(db.is_a_valid_btree(), "invalid after removing {}", id);
+            }
+        }
+        assert!(db.root.is_none());
+    }
+
+    #[test]
+    fn random_adds_and_removes()
+    {
+        let mut rng = Lcg(1);
+        for order in 3..=8
+        {
+            let mut db = DeviceDatabase::new_empty(order);
+            let mut model = BTreeMap::new();
+            for _ in 0..3000
+            {
+                let id = rng.below(300);
+                if rng.below(3) == 0
+                {
+                    let removed = db.remove(id).map(|d| d.path);
+                    assert_eq!(removed, model.remove(&id));
+                } else {
+                    let path = format!("/devices/{}/{}", id, rng.below(10));
+                    let previous = db.add(IoTDevice::new(id, String::new(), path.clone()));
+                    assert_eq!(previous.map(|d| d.path), model.insert(id, path));
+                }
+                assert_matches(&db, &model);
+            }
+            for (id, path) in model.iter()
+            {
+                assert_eq!(db.find(*id).map(|d| d.path), Some(path.clone()));
+            }
+        }
+    }
+
+    #[test]
+    fn add_replaces_existing_devices()
+    {
+        let mut db = DeviceDatabase::new_empty(3);
+        for id in 0..20
+        {
+            assert_eq!(db.add(device(id)), None);
+        }
+        let previous = db.add(IoTDevice::new(7, "10.1.1.1".to_string(), "/moved".to_string()));
+        assert_eq!(previous, Some(device(7)));
+        assert_eq!(db.len(), 20);
+        assert_eq!(ids(&db), (0..20).collect::<Vec<_>>());
+        assert_eq!(db.get(7).map(|d| d.address()), Some("10.1.1.1"));
+        assert!(db.is_a_valid_btree());
+    }
+
+    #[test]
+    fn insert_unique_rejects_duplicates()
+    {
+        let mut db = DeviceDatabase::new_empty(4);
+        assert!(db.insert_unique(device(1)).is_ok());
+        let moved = IoTDevice::new(1, "elsewhere".to_string(), "/".to_string());
+        let err = db.insert_unique(moved.clone()).unwrap_err();
+        assert_eq!(err.0, moved);
+        assert_eq!(err.to_string(), "A device with id 1 already exists");
+        assert_eq!(db.get(1), Some(&device(1)));
+        assert_eq!(db.len(), 1);
+    }
+
+    #[test]
+    fn entry_api()
+    {
+        let mut db = DeviceDatabase::new_empty(3);
+        for id in 0..10
+        {
+            db.add(device(id));
+        }
+
+        db.entry(4).and_modify(|d|
+        {
+            d.set_path("/racks/4".to_string());
+        });
+        assert_eq!(db.get(4).map(|d| d.path()), Some("/racks/4"));
+
+        let new = db.entry(42).or_insert("10.0.0.42".to_string(), "/new".to_string());
+        assert_eq!(new.numerical_id(), 42);
+        let existing = db.entry(3).or_insert_with(|| panic!("device 3 exists"));
+        assert_eq!(existing.path(), "/devices/3");
+
+        match db.entry(5)
+        {
+            Entry::Occupied(mut e) =>
+            {
+                assert_eq!(e.get().address(), "10.0.0.5");
+                let old = e.insert("10.9.9.9".to_string(), "/b".to_string());
+                assert_eq!(old, device(5));
+                assert_eq!(e.get_mut().set_address("10.0.0.55".to_string()), "10.9.9.9");
+            }
+            Entry::Vacant(_) => panic!("device 5 exists")
+        }
+        assert_eq!(db.get(5).map(|d| d.address()), Some("10.0.0.55"));
+
+        match db.entry(8)
+        {
+            Entry::Occupied(e) => assert_eq!(e.remove().numerical_id(), 8),
+            Entry::Vacant(_) => panic!("device 8 exists")
+        }
+        match db.entry(8)
+        {
+            Entry::Vacant(e) => assert_eq!(e.id(), 8),
+            Entry::Occupied(_) => panic!("device 8 was removed")
+        }
+
+        assert_eq!(db.len(), 10);
+        assert_eq!(ids(&db), vec![0, 1, 2, 3, 4, 5, 6, 7, 9, 42]);
+        assert!(db.is_a_valid_btree());
+    }
+
+    fn random_database(rng: &mut Lcg, order: usize, count: u64) -> (DeviceDatabase, Vec<u64>)
+    {
+        let mut db = DeviceDatabase::new_empty(order);
+        let mut model = BTreeMap::new();
+        for _ in 0..count
+        {
+            let id = rng.below(count * 3);
+            db.add(device(id));
+            model.insert(id, ());
+        }
+        (db, model.into_keys().collect())
+    }
+
+    fn bound(rng: &mut Lcg, limit: u64) -> Bound<u64>
+    {
+        match rng.below(3)
+        {
+            0 => Bound::Included(rng.below(limit)),
+            1 => Bound::Excluded(rng.below(limit)),
+            _ => Bound::Unbounded
+        }
+    }
+
+    #[test]
+    fn first_last_floor_ceiling()
+    {
+        let mut db = DeviceDatabase::new_empty(4);
+        assert!(db.first().is_none() && db.last().is_none());
+        assert!(db.floor(5).is_none() && db.ceiling(5).is_none());
+        for id in [10, 20, 30, 40, 50]
+        {
+            db.add(device(id));
+        }
+        let id = |d: Option<&IoTDevice>| d.map(|d| d.numerical_id());
+        assert_eq!(id(db.first()), Some(10));
+        assert_eq!(id(db.last()), Some(50));
+        assert_eq!(id(db.floor(35)), Some(30));
+        assert_eq!(id(db.floor(30)), Some(30));
+        assert_eq!(id(db.floor(9)), None);
+        assert_eq!(id(db.floor(99)), Some(50));
+        assert_eq!(id(db.ceiling(35)), Some(40));
+        assert_eq!(id(db.ceiling(40)), Some(40));
+        assert_eq!(id(db.ceiling(51)), None);
+        assert_eq!(id(db.ceiling(0)), Some(10));
+    }
+
+    #[test]
+    fn floor_and_ceiling_match_model()
+    {
+        let mut rng = Lcg(2);
+        for order in 3..=7
+        {
+            let (db, model) = random_database(&mut rng, order, 200);
+            for x in 0..620
+            {
+                let floor = model.iter().rev().find(|id| **id <= x).copied();
+                let ceiling = model.iter().find(|id| **id >= x).copied();
+                assert_eq!(db.floor(x).map(|d| d.numerical_id), floor);
+                assert_eq!(db.ceiling(x).map(|d| d.numerical_id), ceiling);
+            }
+        }
+    }
+
+    #[test]
+    fn ranges_match_model()
+    {
+        let mut rng = Lcg(3);
+        for order in 3..=7
+        {
+            let (db, model) = random_database(&mut rng, order, 150);
+            for _ in 0..300
+            {
+                let range = (bound(&mut rng, 460), bound(&mut rng, 460));
+                let empty = match range
+                {
+                    (Bound::Included(a), Bound::Included(b)) => a > b,
+                    (Bound::Included(a), Bound::Excluded(b))
+                    | (Bound::Excluded(a), Bound::Included(b))
+                    | (Bound::Excluded(a), Bound::Excluded(b)) => a >= b,
+                    _ => false
+                };
+                if empty
+                {
+                    // BTreeMap panics on these, the database just yields nothing
+                    assert_eq!(db.range(range).count(), 0);
+                    continue;
+                }
+                let expected: Vec<u64> =
+                    model.iter().copied().filter(|id| range.contains(id)).collect();
+                let forward: Vec<u64> = db.range(range).map(|d| d.numerical_id).collect();
+                assert_eq!(forward, expected);
+                let mut backward: Vec<u64> =
+                    db.range(range).rev().map(|d| d.numerical_id).collect();
+                backward.reverse();
+                assert_eq!(backward, expected);
+
+                // Alternating ends meet in the middle without repeating
+                let mut both = db.range(range);
+                let (mut front, mut back) = (vec![], vec![]);
+                while let Some(d) = both.next()
+                {
+                    front.push(d.numerical_id);
+                    match both.next_back()
+                    {
+                        Some(d) => back.push(d.numerical_id),
+                        None => break
+                    }
+                }
+                back.reverse();
+                front.extend(back);
+                assert_eq!(front, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn cursor_steps_both_ways()
+    {
+        let mut rng = Lcg(4);
+        for order in 3..=6
+        {
+            let (db, model) = random_database(&mut rng, order, 120);
+            let mut cursor = db.cursor_front();
+            for id in model.iter()
+            {
+                assert_eq!(cursor.current().map(|d| d.numerical_id), Some(*id));
+                cursor.move_next();
+            }
+            assert!(cursor.current().is_none());
+            cursor.move_prev();
+            assert_eq!(cursor.current().map(|d| d.numerical_id), model.last().copied());
+
+            let mut cursor = db.cursor_back();
+            for id in model.iter().rev()
+            {
+                assert_eq!(cursor.current().map(|d| d.numerical_id), Some(*id));
+                cursor.move_prev();
+            }
+            assert!(cursor.current().is_none());
+            cursor.move_next();
+            assert_eq!(cursor.current().map(|d| d.numerical_id), model.first().copied());
+
+            for _ in 0..50
+            {
+                let x = rng.below(400);
+                let start = model.iter().position(|id| *id >= x);
+                cursor.seek(x);
+                assert_eq!(cursor.current().map(|d| d.numerical_id), start.map(|p| model[p]));
+                if let Some(p) = start
+                {
+                    cursor.move_prev();
+                    let previous = p.checked_sub(1).map(|q| model[q]);
+                    assert_eq!(cursor.current().map(|d| d.numerical_id), previous);
+                    cursor.seek(x);
+                    cursor.move_next();
+                    assert_eq!(cursor.current().map(|d| d.numerical_id), model.get(p + 1).copied());
+                }
+            }
+        }
+        assert!(DeviceDatabase::new_empty(3).cursor_front().current().is_none());
+    }
+
+    #[test]
+    fn iterators_match_model()
+    {
+        let mut rng = Lcg(11);
+        for order in 3..=6
+        {
+            let (db, model) = random_database(&mut rng, order, 150);
+            let mut iter = db.iter();
+            assert_eq!(iter.len(), model.len());
+            iter.next();
+            iter.next_back();
+            assert_eq!(iter.len(), model.len() - 2);
+
+            let mut both_ends = vec![];
+            let mut iter = db.iter();
+            while let Some(front) = iter.next()
+            {
+                both_ends.push(front.numerical_id);
+                if let Some(back) = iter.next_back()
+                {
+                    both_ends.push(back.numerical_id);
+                }
+            }
+            both_ends.sort();
+            assert_eq!(both_ends, model);
+
+            let ids: Vec<_> = (&db).into_iter().map(|d| d.numerical_id).collect();
+            assert_eq!(ids, model);
+            let reversed: Vec<_> = db.iter().rev().map(|d| d.numerical_id).collect();
+            assert_eq!(reversed, model.iter().rev().copied().collect::<Vec<_>>());
+            assert_eq!(db.iter().position(|d| d.numerical_id == model[3]), Some(3));
+
+            let owned: Vec<_> = db.into_iter().collect();
+            assert_eq!(owned, model.iter().map(|id| device(*id)).collect::<Vec<_>>());
+        }
+        assert_eq!(DeviceDatabase::new_empty(3).iter().next(), None);
+    }
+
+    #[test]
+    fn extend_adds_or_replaces()
+    {
+        let mut db = DeviceDatabase::new_empty(4);
+        db.extend((0..30).map(device));
+        db.extend((20..40).map(|id| IoTDevice::new(id, "10.1.1.1".to_string(), "/x".to_string())));
+        assert_eq!(db.len(), 40);
+        assert_eq!(db.iter().len(), 40);
+        assert_eq!(db.get(25).map(|d| d.address()), Some("10.1.1.1"));
+        assert_eq!(ids(&db), (0..40).collect::<Vec<_>>());
+        assert!(db.is_a_valid_btree());
+    }
+
+    #[test]
+    fn collect_uses_the_default_order()
+    {
+        let db = DeviceDatabase::default();
+        assert!(db.is_empty());
+        assert_eq!(db.order, DEFAULT_ORDER);
+
+        let db: DeviceDatabase = (0..50).rev().chain(10..20).map(device).collect();
+        assert_eq!(db.order, DEFAULT_ORDER);
+        assert_eq!(db.len(), 50);
+        assert_eq!(db.iter().len(), 50);
+        assert_eq!(ids(&db), (0..50).collect::<Vec<_>>());
+        assert!(db.is_a_valid_btree());
+    }
+
+    // Every node below the root holds at least `min_devices`, which `remove` relies on
+    fn assert_min_fill(db: &DeviceDatabase, node: &Node, is_root: bool) -> usize
+    {
+        assert!(is_root || node.devices.len() >= db.min_devices());
+        assert!(node.devices.len() < db.order);
+        let children = node.left_child.iter().chain(node.children.iter().flatten());
+        1 + children.map(|c| assert_min_fill(db, c, false)).sum::<usize>()
+    }
+
+    #[test]
+    fn from_sorted_builds_valid_trees()
+    {
+        let mut rng = Lcg(25);
+        for order in 3..=9
         {
-            if let Some(ref k) = node.devices[i]
+            for count in 0..200
             {
-                callback(k);
+                for fill in [1.0, 0.7, 0.0]
+                {
+                    let devices = (0..count).map(|id| device(id * 2));
+                    let db = DeviceDatabase::from_sorted_with_fill(order, fill, devices).unwrap();
+                    let model: BTreeMap<u64, String> =
+                        (0..count).map(|id| (id * 2, String::new())).collect();
+                    assert_matches(&db, &model);
+                    if let Some(root) = db.root.as_ref()
+                    {
+                        assert_min_fill(&db, root, true);
+                    }
+                }
             }
 
-            if let Some(ref c) = node.children[i]
+            let mut db = DeviceDatabase::from_sorted(order, (0..300).map(device)).unwrap();
+            let mut model: BTreeMap<u64, String> = (0..300).map(|id| (id, String::new())).collect();
+            for _ in 0..400
             {
-                self.walk_in_order(&c, callback);
+                let id = rng.below(400);
+                if rng.below(2) == 0
+                {
+                    db.add(device(id));
+                    model.insert(id, String::new());
+                } else {
+                    assert_eq!(db.remove(id).is_some(), model.remove(&id).is_some());
+                }
             }
+            assert_matches(&db, &model);
+        }
+    }
+
+    #[test]
+    fn fill_factor_leaves_room()
+    {
+        let count_nodes = |fill|
+        {
+            let db = DeviceDatabase::from_sorted_with_fill(8, fill, (0..1000).map(device)).unwrap();
+            assert_min_fill(&db, db.root.as_ref().unwrap(), true)
+        };
+        let (packed, half) = (count_nodes(1.0), count_nodes(0.5));
+        assert!(packed < half, "{} nodes packed, {} half full", packed, half);
+        // 126 full leaves, 125 separators in 16 parents, 15 in 2 and 1 in the root
+        assert_eq!(packed, 126 + 16 + 2 + 1);
+    }
+
+    #[test]
+    fn from_sorted_rejects_unsorted_input()
+    {
+        let result = DeviceDatabase::from_sorted(4, [1, 2, 2, 3].iter().map(|id| device(*id)));
+        assert_eq!(result.err(), Some(FromSortedError::Duplicate(2)));
+
+        let result = DeviceDatabase::from_sorted(4, [1, 5, 3].iter().map(|id| device(*id)));
+        let error = result.err().unwrap();
+        assert_eq!(error, FromSortedError::OutOfOrder { previous: 5, next: 3 });
+        assert_eq!(error.to_string(), "Device id 3 comes after 5, input must be sorted");
+    }
+
+    #[test]
+    fn from_sorted_rejects_small_orders()
+    {
+        for order in 0..3
+        {
+            let result = DeviceDatabase::from_sorted(order, (0..10).map(device));
+            assert_eq!(result.err(), Some(FromSortedError::InvalidOrder(order)));
         }
+        let error = DeviceDatabase::from_sorted(2, vec![]).err().unwrap();
+        assert_eq!(error.to_string(), "Order 2 is too small, a B-tree needs at least 3");
+        assert!(DeviceDatabase::from_sorted(3, (0..10).map(device)).is_ok());
     }
-}
\ No newline at end of file
+}
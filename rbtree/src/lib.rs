@@ -4,7 +4,8 @@ pub use btree::IoTDevice;
 use std::rc::Rc;
 use std::cell::RefCell;
 use std::cmp;
-use std::mem;
+use std::iter::FromIterator;
+use std::marker::PhantomData;
 
 type BareTree = Rc<RefCell<Node>>;
 type Tree = Option<BareTree>;
@@ -71,7 +72,7 @@ impl RBTree {
 
     pub fn add(&mut self, device: IoTDevice) {
         self.length += 1;
-        let root = mem::replace(&mut self.root, None);
+        let root = self.root.take();
         let new_tree = self.add_r(root, device);
         self.root = self.fix_tree(new_tree.1)
     }
@@ -92,17 +93,52 @@ impl RBTree {
         )
     }
 
-    pub fn walk(&self, callback: impl Fn(&IoTDevice) -> ()) {
-        self.walk_in_order(&self.root, &callback);
+    pub fn len(&self) -> usize {
+        self.length
     }
 
-    fn walk_in_order(&self, node: &Tree, callback: &impl Fn(&IoTDevice) ->()) {
-        if let Some(n) = node {
-            let n = n.borrow();
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    pub fn walk(&self, mut callback: impl FnMut(&IoTDevice)) {
+        self.iter_cloned().for_each(|device| callback(&device));
+    }
 
-            self.walk_in_order(&n.left, callback);
-            callback(&n.dev);
-            self.walk_in_order(&n.right,callback);
+    // In-order like `walk`, which puts larger ids first in this tree. Unlike
+    // the other trees' `iter`, this yields owned copies: devices live behind
+    // `RefCell`s, and a borrow of one can't outlive the guard it came from.
+    pub fn iter_cloned(&self) -> IterCloned<'_> {
+        let mut iter = IterCloned {
+            front: vec![],
+            back: vec![],
+            remaining: self.len(),
+            tree: PhantomData
+        };
+        iter.push_left(self.root.clone());
+        iter.push_right(self.root.clone());
+        iter
+    }
+
+    fn into_in_order(node: Tree, devices: &mut Vec<IoTDevice>) {
+        if let Some(n) = node {
+            // Cut every link to and from this node first, so `n` ends up as
+            // its only owner
+            let (left, right) = {
+                let mut n = n.borrow_mut();
+                n.parent = None;
+                (n.left.take(), n.right.take())
+            };
+            for child in left.iter().chain(right.iter()) {
+                child.borrow_mut().parent = None;
+            }
+            RBTree::into_in_order(left, devices);
+            let dev = match Rc::try_unwrap(n) {
+                Ok(node) => node.into_inner().dev,
+                Err(shared) => shared.borrow().dev.clone()
+            };
+            devices.push(dev);
+            RBTree::into_in_order(right, devices);
         }
     }
 
@@ -113,7 +149,7 @@ impl RBTree {
                 if n.dev.numerical_id == dev.numerical_id {
                     Some(n.dev.clone())
                 } else {
-                    match self.check(&n.dev, &dev) {
+                    match self.check(&n.dev, dev) {
                         RBOperation::LeftNode => self.find_r(&n.left, dev),
                         RBOperation::RightNode => self.find_r(&n.right, dev)
                     }
@@ -206,10 +242,9 @@ impl RBTree {
                         RBOperation::LeftNode => {
                             // uncle is on the left
                             let mut parent = n.borrow().parent.as_ref().unwrap().clone();
-                            if uncle.is_some()
-                                && uncle.as_ref().unwrap().borrow().color == Color::Red
+                            if let Some(uncle) =
+                                uncle.filter(|u| u.borrow().color == Color::Red)
                             {
-                                let uncle = uncle.unwrap();
                                 parent.borrow_mut().color = Color::Black;
                                 uncle.borrow_mut().color = Color::Black;
                                 parent.borrow().parent.as_ref().unwrap().borrow_mut().color =
@@ -260,10 +295,9 @@ impl RBTree {
                             // uncle is on the right
                             let mut parent = n.borrow().parent.as_ref().unwrap().clone();
 
-                            if uncle.is_some()
-                                && uncle.as_ref().unwrap().borrow().color == Color::Red
+                            if let Some(uncle) =
+                                uncle.filter(|u| u.borrow().color == Color::Red)
                             {
-                                let uncle = uncle.unwrap();
 
                                 parent.borrow_mut().color = Color::Black;
                                 uncle.borrow_mut().color = Color::Black;
@@ -315,10 +349,7 @@ impl RBTree {
         } else {
             Some(inserted)
         };
-        root.map(|r| {
-            r.borrow_mut().color = Color::Black;
-            r
-        })
+        root.inspect(|r| r.borrow_mut().color = Color::Black)
     }
 
     fn rotate(&self, node: BareTree, direction: Rotation) {
@@ -331,11 +362,10 @@ impl RBTree {
                     _ => None
                 };
 
-                if y.is_some() {
-                    y.as_ref().unwrap().borrow_mut().parent = x.borrow().parent.clone();
-                    if y.as_ref().unwrap().borrow_mut().right.is_some() {
-                        let r = y.as_ref().unwrap().borrow().right.clone();
-                        r.unwrap().borrow_mut().parent = Some(x.clone());
+                if let Some(ref y) = y {
+                    y.borrow_mut().parent = x.borrow().parent.clone();
+                    if let Some(ref r) = y.borrow().right {
+                        r.borrow_mut().parent = Some(x.clone());
                     }
                 }
 
@@ -359,12 +389,11 @@ impl RBTree {
                     _ => None,
                 };
 
-                if y.is_some() {
-                    y.as_ref().unwrap().borrow_mut().parent = x.borrow().parent.clone();
+                if let Some(ref y) = y {
+                    y.borrow_mut().parent = x.borrow().parent.clone();
 
-                    if y.as_ref().unwrap().borrow().left.is_some() {
-                        let l = y.as_ref().unwrap().borrow().left.clone();
-                        l.unwrap().borrow_mut().parent = Some(x.clone());
+                    if let Some(ref l) = y.borrow().left {
+                        l.borrow_mut().parent = Some(x.clone());
                     }
                 }
 
@@ -412,4 +441,178 @@ impl RBTree {
     fn parent_color(&self, n: &BareTree) -> Color {
         n.borrow().parent.as_ref().unwrap().borrow().color.clone()
     }
-}
\ No newline at end of file
+}
+
+pub struct IterCloned<'a> {
+    front: Vec<BareTree>,
+    back: Vec<BareTree>,
+    // Both stacks may hold the same nodes, this keeps the ends from crossing
+    remaining: usize,
+    // Keeps the tree borrowed, so it can't change while being iterated
+    tree: PhantomData<&'a RBTree>
+}
+
+impl<'a> IterCloned<'a> {
+    fn push_left(&mut self, mut tree: Tree) {
+        while let Some(n) = tree {
+            tree = n.borrow().left.clone();
+            self.front.push(n);
+        }
+    }
+
+    fn push_right(&mut self, mut tree: Tree) {
+        while let Some(n) = tree {
+            tree = n.borrow().right.clone();
+            self.back.push(n);
+        }
+    }
+}
+
+impl<'a> Iterator for IterCloned<'a> {
+    type Item = IoTDevice;
+
+    fn next(&mut self) -> Option<IoTDevice> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let n = self.front.pop()?;
+        self.remaining -= 1;
+        let n = n.borrow();
+        self.push_left(n.right.clone());
+        Some(n.dev.clone())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a> DoubleEndedIterator for IterCloned<'a> {
+    fn next_back(&mut self) -> Option<IoTDevice> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let n = self.back.pop()?;
+        self.remaining -= 1;
+        let n = n.borrow();
+        self.push_right(n.left.clone());
+        Some(n.dev.clone())
+    }
+}
+
+impl<'a> ExactSizeIterator for IterCloned<'a> {}
+
+pub struct IntoIter {
+    devices: std::vec::IntoIter<IoTDevice>
+}
+
+impl Iterator for IntoIter {
+    type Item = IoTDevice;
+
+    fn next(&mut self) -> Option<IoTDevice> {
+        self.devices.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.devices.size_hint()
+    }
+}
+
+impl DoubleEndedIterator for IntoIter {
+    fn next_back(&mut self) -> Option<IoTDevice> {
+        self.devices.next_back()
+    }
+}
+
+impl ExactSizeIterator for IntoIter {}
+
+impl IntoIterator for RBTree {
+    type Item = IoTDevice;
+    type IntoIter = IntoIter;
+
+    fn into_iter(self) -> IntoIter {
+        let mut devices = Vec::with_capacity(self.len());
+        RBTree::into_in_order(self.root, &mut devices);
+        IntoIter {
+            devices: devices.into_iter()
+        }
+    }
+}
+
+impl FromIterator<IoTDevice> for RBTree {
+    fn from_iter<I: IntoIterator<Item = IoTDevice>>(devices: I) -> RBTree {
+        let mut tree = RBTree::new_empty();
+        tree.extend(devices);
+        tree
+    }
+}
+
+impl Extend<IoTDevice> for RBTree {
+    fn extend<I: IntoIterator<Item = IoTDevice>>(&mut self, devices: I) {
+        for device in devices {
+            self.add(device);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn device(id: u64) -> IoTDevice {
+        IoTDevice::new(id, format!("10.0.0.{}", id), format!("/devices/{}", id))
+    }
+
+    fn ids(devices: impl Iterator<Item = IoTDevice>) -> Vec<u64> {
+        devices.map(|d| d.numerical_id).collect()
+    }
+
+    #[test]
+    fn iterators() {
+        let tree: RBTree = (0..200).map(|id| device(id * 7 % 200)).collect();
+        assert!(tree.is_a_valid_red_blacK_tree());
+        let descending: Vec<u64> = (0..200).rev().collect();
+        assert_eq!(ids(tree.iter_cloned()), descending);
+        assert_eq!(ids(tree.iter_cloned().rev().take(3)), vec![0, 1, 2]);
+
+        let mut iter = tree.iter_cloned();
+        assert_eq!(iter.len(), 200);
+        let mut both_ends = vec![];
+        while let Some(front) = iter.next() {
+            both_ends.push(front.numerical_id);
+            if let Some(back) = iter.next_back() {
+                both_ends.push(back.numerical_id);
+            }
+            assert_eq!(iter.len(), 200 - both_ends.len());
+        }
+        both_ends.sort();
+        assert_eq!(both_ends, (0..200).collect::<Vec<_>>());
+
+        let owned: Vec<IoTDevice> = tree.into_iter().collect();
+        assert_eq!(owned.len(), 200);
+        assert_eq!(owned[0], device(199));
+    }
+
+    #[test]
+    fn into_iter_after_borrowing() {
+        let tree: RBTree = (0..50).map(device).collect();
+        let mut iter = tree.iter_cloned();
+        assert_eq!(iter.next(), tree.find(49));
+        assert_eq!(iter.next_back(), tree.find(0));
+        drop(iter);
+        let owned: Vec<u64> = tree.into_iter().map(|d| d.numerical_id).collect();
+        assert_eq!(owned, (0..50).rev().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn extend() {
+        let mut tree = RBTree::new_empty();
+        assert_eq!(tree.iter_cloned().next(), None);
+        tree.extend(vec![device(3), device(1)]);
+        tree.extend((4..8).map(device));
+        assert_eq!(tree.length, 6);
+        assert_eq!(tree.len(), 6);
+        assert_eq!(ids(tree.iter_cloned().rev()), vec![1, 3, 4, 5, 6, 7]);
+        assert_eq!(tree.find(5).map(|d| d.path), Some("/devices/5".to_string()));
+    }
+}